@@ -0,0 +1,360 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+use common::{solve, Answer};
+use common::op_code::{self, Arithmetic, Cfg, Debugger, Program, Vm};
+use common::input::{
+    input_dir, CachedSource, FileCache, HttpFetcher, InputError, InputSource, PathSource, AOC_URL,
+    SESSION_FILE,
+};
+
+mod day01;
+mod day02;
+mod day03;
+mod day04;
+mod day05;
+mod day06;
+mod day07;
+mod day08;
+mod day09;
+mod day10;
+mod day11;
+mod day12;
+mod day13;
+mod day14;
+mod day15;
+mod day16;
+mod day17;
+mod day18;
+mod day19;
+mod day20;
+mod day21;
+mod day22;
+mod day23;
+mod day24;
+mod day25;
+
+type Runner = fn(String, Option<u8>) -> Vec<Answer>;
+
+static DAYS: [(u8, Runner); 25] = [
+    (1, solve::<day01::Day01>),
+    (2, solve::<day02::Day02>),
+    (3, solve::<day03::Day03>),
+    (4, solve::<day04::Day04>),
+    (5, solve::<day05::Day05>),
+    (6, solve::<day06::Day06>),
+    (7, solve::<day07::Day07>),
+    (8, solve::<day08::Day08>),
+    (9, solve::<day09::Day09>),
+    (10, solve::<day10::Day10>),
+    (11, solve::<day11::Day11>),
+    (12, solve::<day12::Day12>),
+    (13, solve::<day13::Day13>),
+    (14, solve::<day14::Day14>),
+    (15, solve::<day15::Day15>),
+    (16, solve::<day16::Day16>),
+    (17, solve::<day17::Day17>),
+    (18, solve::<day18::Day18>),
+    (19, solve::<day19::Day19>),
+    (20, solve::<day20::Day20>),
+    (21, solve::<day21::Day21>),
+    (22, solve::<day22::Day22>),
+    (23, solve::<day23::Day23>),
+    (24, solve::<day24::Day24>),
+    (25, solve::<day25::Day25>),
+];
+
+const USAGE: &str = "Usage: aoc run <day|all> [--part <1|2>] [--input-dir <dir>] [--fetch] [--input <path|->]
+       aoc debug <program> [--registers <n>] [--trace <path>] [--arithmetic <wrapping|checked|bits:N>]
+       aoc cfg <program>
+       aoc battle <day 15 input> [--elf-attack <n>] [--json]
+       aoc record <day 15 input> <replay>
+       aoc replay <replay>";
+
+enum Command {
+    Run(Args),
+    Debug(DebugArgs),
+    Cfg(PathBuf),
+    Battle(BattleArgs),
+    Record(PathBuf, PathBuf),
+    Replay(PathBuf),
+}
+
+struct Args {
+    days: Vec<u8>,
+    part: Option<u8>,
+    input_dir: PathBuf,
+    fetch: bool,
+    input: Option<PathBuf>,
+}
+
+struct DebugArgs {
+    program: PathBuf,
+    registers: usize,
+    trace: Option<PathBuf>,
+    arithmetic: Arithmetic,
+}
+
+struct BattleArgs {
+    input: PathBuf,
+    elf_attack: Option<i16>,
+    json: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item=String>) -> Result<Command, String> {
+    match args.next().as_deref() {
+        Some("run") => parse_run(args).map(Command::Run),
+        Some("debug") => parse_debug(args).map(Command::Debug),
+        Some("cfg") => match (args.next(), args.next()) {
+            (Some(program), None) => Ok(Command::Cfg(PathBuf::from(program))),
+            (None, _) => Err("Missing program".to_string()),
+            (_, Some(other)) => Err(format!("Unknown argument {:?}", other)),
+        },
+        Some("battle") => parse_battle(args).map(Command::Battle),
+        Some("record") => match (args.next(), args.next(), args.next()) {
+            (Some(input), Some(replay), None) => Ok(Command::Record(PathBuf::from(input), PathBuf::from(replay))),
+            (None, _, _) | (_, None, _) => Err("Missing input or replay".to_string()),
+            (_, _, Some(other)) => Err(format!("Unknown argument {:?}", other)),
+        },
+        Some("replay") => match (args.next(), args.next()) {
+            (Some(replay), None) => Ok(Command::Replay(PathBuf::from(replay))),
+            (None, _) => Err("Missing replay".to_string()),
+            (_, Some(other)) => Err(format!("Unknown argument {:?}", other)),
+        },
+        Some(cmd) => Err(format!("Unknown command {:?}", cmd)),
+        None => Err("Missing command".to_string()),
+    }
+}
+
+fn parse_debug(mut args: impl Iterator<Item=String>) -> Result<DebugArgs, String> {
+    let program = PathBuf::from(args.next().ok_or("Missing program")?);
+    let mut registers = 6;
+    let mut trace = None;
+    let mut arithmetic = Arithmetic::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--registers" | "-r" => registers = match args.next().map(|n| n.parse::<usize>()) {
+                Some(Ok(n)) if n > 0 => n,
+                _ => return Err("Invalid register count".to_string()),
+            },
+            "--trace" => trace = match args.next() {
+                Some(path) => Some(PathBuf::from(path)),
+                None => return Err("Missing path for --trace".to_string()),
+            },
+            "--arithmetic" => arithmetic = match args.next() {
+                Some(mode) => mode.parse()?,
+                None => return Err("Missing mode for --arithmetic".to_string()),
+            },
+            other => return Err(format!("Unknown argument {:?}", other)),
+        }
+    }
+    Ok(DebugArgs { program, registers, trace, arithmetic })
+}
+
+fn parse_battle(mut args: impl Iterator<Item=String>) -> Result<BattleArgs, String> {
+    let input = PathBuf::from(args.next().ok_or("Missing input")?);
+    let mut elf_attack = None;
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--elf-attack" => elf_attack = match args.next().map(|n| n.parse::<i16>()) {
+                Some(Ok(n)) if n > 0 => Some(n),
+                _ => return Err("Invalid attack power".to_string()),
+            },
+            "--json" => json = true,
+            other => return Err(format!("Unknown argument {:?}", other)),
+        }
+    }
+    Ok(BattleArgs { input, elf_attack, json })
+}
+
+fn parse_run(mut args: impl Iterator<Item=String>) -> Result<Args, String> {
+    let days = match args.next().as_deref() {
+        Some("all") => DAYS.iter().map(|&(day, _)| day).collect(),
+        Some(day) => match day.parse::<u8>() {
+            Ok(d) if DAYS.iter().any(|&(day, _)| day == d) => vec![d],
+            _ => return Err(format!("No solution for day {:?}", day)),
+        },
+        None => return Err("Missing day".to_string()),
+    };
+    let mut part = None;
+    let mut dir = input_dir();
+    let mut fetch = false;
+    let mut input = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--part" | "-p" => part = match args.next().as_deref() {
+                Some("1") => Some(1),
+                Some("2") => Some(2),
+                other => return Err(format!("Invalid part {:?}", other)),
+            },
+            "--input-dir" => dir = match args.next() {
+                Some(path) => PathBuf::from(path),
+                None => return Err("Missing directory for --input-dir".to_string()),
+            },
+            "--fetch" => fetch = true,
+            "--input" | "-i" => input = match args.next() {
+                Some(path) => Some(PathBuf::from(path)),
+                None => return Err("Missing path for --input".to_string()),
+            },
+            other => return Err(format!("Unknown argument {:?}", other)),
+        }
+    }
+    if input.is_some() && days.len() != 1 {
+        return Err("--input can only be used with a single day".to_string());
+    }
+    Ok(Args { days, part, input_dir: dir, fetch, input })
+}
+
+/// Reads the `--input` file if given, otherwise cached inputs, downloading
+/// missing ones with the session in `.token` when `--fetch` is given.
+fn input_source(args: &Args) -> Result<Box<dyn InputSource>, InputError> {
+    if let Some(path) = &args.input {
+        return Ok(Box::new(PathSource::new(path)));
+    }
+    let cache = FileCache::new(&args.input_dir);
+    if !args.fetch {
+        return Ok(Box::new(cache));
+    }
+    let fetcher = HttpFetcher::from_token_file(AOC_URL, &args.input_dir.join(SESSION_FILE))?;
+    Ok(Box::new(CachedSource { cache, fetcher }))
+}
+
+/// Runs every requested day, returning false if any input could not be loaded.
+fn run(args: &Args, source: &dyn InputSource) -> bool {
+    let mut total = Duration::from_secs(0);
+    let mut success = true;
+    for &(day, runner) in DAYS.iter().filter(|(d, _)| args.days.contains(d)) {
+        println!("Day {:02}", day);
+        let text = match source.fetch(day, 2018) {
+            Ok(text) => text,
+            Err(err) => {
+                eprintln!("  {}", err);
+                success = false;
+                continue;
+            }
+        };
+        for answer in runner(text, args.part) {
+            println!("  Part {}: {} ({:.3?})", answer.part, answer.value, answer.elapsed);
+            total += answer.elapsed;
+        }
+    }
+    if args.days.len() > 1 {
+        println!("Total: {:.3?}", total);
+    }
+    success
+}
+
+fn load_program(path: &Path, registers: usize) -> Result<Program, String> {
+    let text = read(path)?;
+    op_code::load(&text, registers).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Loads an ElfCode program and hands stdin to the debugger.
+fn debug(args: &DebugArgs) -> Result<(), String> {
+    let program = load_program(&args.program, args.registers)?;
+    let mut vm = Vm::new(&program, args.registers);
+    vm.arithmetic = args.arithmetic;
+    let mut debugger = Debugger::new(vm);
+    if let Some(path) = &args.trace {
+        let file = fs::File::create(path)
+            .map_err(|err| format!("Cannot write {}: {}", path.display(), err))?;
+        debugger.trace_to(Box::new(io::BufWriter::new(file)));
+    }
+    debugger.repl(io::stdin().lock(), io::stdout()).map_err(|err| err.to_string())
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))
+}
+
+/// Fights a day 15 battle and prints how every unit fared.
+fn battle(args: &BattleArgs) -> Result<(), String> {
+    let outcome = day15::battle(read(&args.input)?, args.elf_attack);
+    match args.json {
+        true => println!("{}", outcome.to_json()),
+        false => println!("{}", outcome),
+    }
+    Ok(())
+}
+
+/// Fights a day 15 battle, writing each round to a replay file.
+fn record(input: &Path, replay: &Path) -> Result<(), String> {
+    let text = read(input)?;
+    let file = fs::File::create(replay)
+        .map_err(|err| format!("Cannot write {}: {}", replay.display(), err))?;
+    let score = day15::record(text, &mut io::BufWriter::new(file)).map_err(|err| err.to_string())?;
+    println!("Outcome: {}", score);
+    Ok(())
+}
+
+fn replay(path: &Path) -> Result<(), String> {
+    let frames = day15::load_replay(&read(path)?)
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    day15::play(&frames, io::stdin().lock(), io::stdout()).map_err(|err| err.to_string())
+}
+
+fn main() {
+    match parse_args(env::args().skip(1)) {
+        Ok(Command::Run(args)) => match input_source(&args) {
+            Ok(source) => if !run(&args, source.as_ref()) {
+                process::exit(1);
+            },
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        },
+        Ok(Command::Debug(args)) => if let Err(message) = debug(&args) {
+            eprintln!("{}", message);
+            process::exit(1);
+        },
+        Ok(Command::Cfg(path)) => match load_program(&path, usize::MAX) {
+            Ok(program) => print!("{}", Cfg::new(&program).to_dot()),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
+        },
+        Ok(Command::Battle(args)) => if let Err(message) = battle(&args) {
+            eprintln!("{}", message);
+            process::exit(1);
+        },
+        Ok(Command::Record(input, path)) => if let Err(message) = record(&input, &path) {
+            eprintln!("{}", message);
+            process::exit(1);
+        },
+        Ok(Command::Replay(path)) => if let Err(message) = replay(&path) {
+            eprintln!("{}", message);
+            process::exit(1);
+        },
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_arithmetic_spellings_parse() {
+        let debug = |mode: &str| parse_args(["debug", "p.elf", "--arithmetic", mode].iter().map(|s| s.to_string()));
+        let listed = USAGE.split("--arithmetic <").nth(1).and_then(|rest| rest.split('>').next()).unwrap();
+        for spelling in listed.split('|') {
+            let spelling = spelling.replace(":N", ":32");
+            match debug(&spelling) {
+                Ok(Command::Debug(args)) => assert_eq!(args.arithmetic.to_string(), spelling),
+                _ => panic!("Usage lists {:?} but it does not parse", spelling),
+            }
+        }
+        assert!(debug("bits").is_err());
+        assert!(debug("bits:0").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+#[macro_use]
+extern crate bencher;
+
+use bencher::Bencher;
+
+use common::fuzz::Rng;
+use common::op_code::{Compiled, Program, Vm};
+use common::Solution;
+
+#[allow(dead_code, unused_imports)]
+#[path = "day15.rs"]
+mod day15;
+
+// Programs in the shape of the day 19 and day 21 inputs.
+const DAY19: &str = "
+#ip 4
+addi 4 16 4
+seti 1 5 1
+seti 1 2 2
+mulr 1 2 3
+eqrr 3 5 3
+addr 3 4 4
+addi 4 1 4
+addr 1 0 0
+addi 2 1 2
+gtrr 2 5 3
+addr 4 3 4
+seti 2 7 4
+addi 1 1 1
+gtrr 1 5 3
+addr 3 4 4
+seti 1 9 4
+mulr 4 4 4
+addi 5 2 5
+mulr 5 5 5
+mulr 4 5 5
+muli 5 11 5
+addi 3 1 3
+mulr 3 4 3
+addi 3 18 3
+addr 5 3 5
+addr 4 0 4
+seti 0 3 4
+setr 4 2 3
+mulr 3 4 3
+addr 4 3 3
+mulr 4 3 3
+muli 3 14 3
+mulr 3 4 3
+addr 5 3 5
+seti 0 6 0
+seti 0 7 4
+";
+
+const DAY21: &str = "
+#ip 2
+seti 123 0 3
+bani 3 456 3
+eqri 3 72 3
+addr 3 2 2
+seti 0 0 2
+seti 0 2 3
+bori 3 65536 5
+seti 5557974 2 3
+bani 5 255 4
+addr 3 4 3
+bani 3 16777215 3
+muli 3 65899 3
+bani 3 16777215 3
+gtir 256 5 4
+addr 4 2 2
+addi 2 1 2
+seti 27 3 2
+seti 0 1 4
+addi 4 1 1
+muli 1 256 1
+gtrr 1 5 1
+addr 1 2 2
+addi 2 1 2
+seti 25 2 2
+addi 4 1 4
+seti 17 7 2
+setr 4 2 5
+seti 7 8 2
+eqrr 3 0 4
+addr 4 2 2
+seti 5 6 2
+";
+
+/// Day 21 candidates are the values compared with r0 at instruction 28.
+const DAY21_CANDIDATES: usize = 10;
+
+/// A walled `size` by `size` cave with scattered rock and `units` units,
+/// alternately elves and goblins.
+fn cave(size: usize, units: usize, seed: u64) -> String {
+    let mut rng = Rng::new(seed);
+    let mut cells = vec![vec!['#'; size]; size];
+    for row in cells.iter_mut().take(size - 1).skip(1) {
+        for cell in row.iter_mut().take(size - 1).skip(1) {
+            if rng.below(8) != 0 {
+                *cell = '.';
+            }
+        }
+    }
+    let mut placed = 0;
+    while placed < units {
+        let (x, y) = (1 + rng.below(size - 2), 1 + rng.below(size - 2));
+        if cells[y][x] == '.' {
+            cells[y][x] = if placed % 2 == 0 { 'E' } else { 'G' };
+            placed += 1;
+        }
+    }
+    cells.iter().map(|row| row.iter().collect::<String>()).collect::<Vec<_>>().join("\n")
+}
+
+fn day15_cave(bench: &mut Bencher) {
+    let input = day15::Day15::parse(cave(64, 200, 15));
+    bench.iter(|| day15::Day15::part1(&input));
+}
+
+fn day19_vm(bench: &mut Bencher) {
+    let program: Program = DAY19.parse().unwrap();
+    bench.iter(|| {
+        let mut vm = Vm::new(&program, 6);
+        vm.run();
+        vm.registers[0]
+    });
+}
+
+fn day19_compiled(bench: &mut Bencher) {
+    let program = Compiled::new(&DAY19.parse().unwrap());
+    bench.iter(|| {
+        let mut registers = [0; 6];
+        program.run(&mut registers);
+        registers[0]
+    });
+}
+
+fn day19_optimized(bench: &mut Bencher) {
+    let program: Program = DAY19.parse().unwrap();
+    bench.iter(|| {
+        let mut vm = Vm::new(&program, 6);
+        vm.optimize();
+        vm.run();
+        vm.registers[0]
+    });
+}
+
+fn day21_vm(bench: &mut Bencher) {
+    let program: Program = DAY21.parse().unwrap();
+    bench.iter(|| {
+        let mut vm = Vm::new(&program, 6);
+        let mut seen = 0;
+        vm.run_until(|vm| {
+            seen += (vm.ip == 28) as usize;
+            seen > DAY21_CANDIDATES
+        });
+        vm.registers[3]
+    });
+}
+
+fn day21_compiled(bench: &mut Bencher) {
+    let program = Compiled::new(&DAY21.parse().unwrap());
+    bench.iter(|| {
+        let mut registers = [0; 6];
+        let mut seen = 0;
+        program.run_until(&mut registers, 0, |ip, _| {
+            seen += (ip == 28) as usize;
+            seen > DAY21_CANDIDATES
+        });
+        registers[3]
+    });
+}
+
+benchmark_group!(elfcode, day19_vm, day19_compiled, day19_optimized, day21_vm, day21_compiled);
+benchmark_group!(combat, day15_cave);
+benchmark_main!(elfcode, combat);
@@ -1,25 +1,23 @@
-extern crate common;
-
 use std::collections::HashSet;
 use std::vec::Vec;
 
-use common::get_input;
+use common::Solution;
 
-fn part1(input: &Vec<i32>) -> i32 {
-    return input.into_iter().sum();
+fn part1(input: &[i32]) -> i32 {
+    input.iter().sum()
 }
 
-fn part2(input: &Vec<i32>) -> i32 {
+fn part2(input: &[i32]) -> i32 {
     let mut total: i32 = 0;
     let mut seen: HashSet<i32> = HashSet::new();
-    for line in input.into_iter().cycle() {
+    for line in input.iter().cycle() {
         if seen.contains(&total) {
             break;
         }
         seen.insert(total);
         total += line;
     }
-    return total;
+    total
 }
 
 fn parse(lines: String) -> Vec<i32> {
@@ -27,11 +25,25 @@ fn parse(lines: String) -> Vec<i32> {
     for line in lines.trim().split("\n") {
         result.push(line.parse::<i32>().unwrap());
     }
-    return result;
+    result
 }
 
-fn main() {
-    let input = parse(get_input(01, 2018));
-    print!("Part 1: {}\n", part1(&input));
-    print!("Part 2: {}\n", part2(&input));
+pub struct Day01;
+
+impl Solution for Day01 {
+    type Input = Vec<i32>;
+    type Part1 = i32;
+    type Part2 = i32;
+
+    fn parse(text: String) -> Vec<i32> {
+        parse(text)
+    }
+
+    fn part1(input: &Vec<i32>) -> i32 {
+        part1(input)
+    }
+
+    fn part2(input: &Vec<i32>) -> i32 {
+        part2(input)
+    }
 }
@@ -1,10 +1,8 @@
-extern crate common;
-
 use std::collections::HashMap;
 
-use common::get_input;
+use common::Solution;
 
-fn part1(lines: &Vec<String>) -> u32 {
+fn part1(lines: &[String]) -> u32 {
     let mut threes = 0;
     let mut twos = 0;
     for line in lines {
@@ -13,15 +11,14 @@ fn part1(lines: &Vec<String>) -> u32 {
         if counts.values().any(|&v| v == 3) { threes += 1 }
         if counts.values().any(|&v| v == 2) { twos += 1 }
     }
-    return threes * twos;
+    threes * twos
 }
 
-fn part2(lines: &Vec<String>) -> String {
-    for (l, head) in lines.into_iter().enumerate() {
-        for tail in lines.into_iter().skip(l+1) {
+fn part2(lines: &[String]) -> String {
+    for (l, head) in lines.iter().enumerate() {
+        for tail in lines.iter().skip(l+1) {
             let same:String = head
                 .chars()
-                .into_iter()
                 .zip(tail.chars())
                 .filter_map(
                     |(a, b)| if a == b { Some(a) } else { None })
@@ -35,16 +32,29 @@ fn part2(lines: &Vec<String>) -> String {
 }
 
 fn parse(lines: String) -> Vec<String> {
-    return lines
+    lines
         .trim()
         .split("\n")
-        .into_iter()
         .map(|s| s.to_string())
-        .collect();
+        .collect()
 }
 
-fn main() {
-    let input = parse(get_input(02, 2018));
-    print!("Part 1: {}\n", part1(&input));
-    print!("Part 2: {}\n", part2(&input));
+pub struct Day02;
+
+impl Solution for Day02 {
+    type Input = Vec<String>;
+    type Part1 = u32;
+    type Part2 = String;
+
+    fn parse(text: String) -> Vec<String> {
+        parse(text)
+    }
+
+    fn part1(input: &Vec<String>) -> u32 {
+        part1(input)
+    }
+
+    fn part2(input: &Vec<String>) -> String {
+        part2(input)
+    }
 }
@@ -1,16 +1,15 @@
-extern crate common;
-extern crate regex;
-
-use std::str;
 use std::cmp::{min,max};
 use std::collections::HashSet;
 
-use common::get_input;
 use regex::Regex;
 
+use common::point::PointN;
+use common::Solution;
+
+type Point = PointN<i32, 2>;
 
 #[derive(Debug, PartialEq)]
-struct Claim {
+pub struct Claim {
     id: u32,
     patch: Patch,
 }
@@ -25,16 +24,17 @@ impl Iterator for PatchIterator {
     type Item = Point;
 
     fn next(&mut self) -> Option<Point> {
-        if self.stop.y <= self.current.y {
+        if self.stop.y() <= self.current.y() {
             return None;
         }
-        let result = Some(self.current.clone());
-        self.current.x += 1;
-        if self.stop.x <= self.current.x {
-            self.current.x = self.start.x;
-            self.current.y += 1;
-        }
-        return result;
+        let result = Some(self.current);
+        let (x, y) = (self.current.x() + 1, self.current.y());
+        self.current = if self.stop.x() <= x {
+            Point::new([self.start.x(), y + 1])
+        } else {
+            Point::new([x, y])
+        };
+        result
     }
 }
 
@@ -46,17 +46,16 @@ struct Patch {
 
 impl Patch {
     fn overlap(&self, other: &Patch) -> Option<Patch> {
-        let min_x = max(self.point.x, other.point.x);
-        let min_y = max(self.point.y, other.point.y);
-        let max_x = min(self.point.x + self.size.x, other.point.x + other.size.x);
-        let max_y = min(self.point.y + self.size.y, other.point.y + other.size.y);
-        if min_x < max_x && min_y < max_y {
-            return Some(Patch{
-                point: Point { x: min_x, y: min_y },
-                size: Point { x: max_x - min_x, y: max_y - min_y },
-            });
+        let start = Point::new([
+            max(self.point.x(), other.point.x()),
+            max(self.point.y(), other.point.y()),
+        ]);
+        let (a, b) = (self.point + self.size, other.point + other.size);
+        let stop = Point::new([min(a.x(), b.x()), min(a.y(), b.y())]);
+        if start.x() < stop.x() && start.y() < stop.y() {
+            return Some(Patch { point: start, size: stop - start });
         }
-        return None;
+        None
     }
 }
 
@@ -65,36 +64,18 @@ impl IntoIterator for Patch {
     type IntoIter = PatchIterator;
 
     fn into_iter(self) -> PatchIterator {
-        return PatchIterator {
-            current: self.point.clone(),
-            start: self.point.clone(),
-            stop: Point {
-                x: self.point.x + self.size.x,
-                y: self.point.y + self.size.y,
-            }
+        PatchIterator {
+            current: self.point,
+            start: self.point,
+            stop: self.point + self.size,
         }
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
-struct Point {
-    x: i32,
-    y: i32,
-}
-
-impl Point {
-    fn from_str(x: &str, y: &str) -> Point {
-        return Point {
-            x: x.parse().unwrap(),
-            y: y.parse().unwrap(),
-        }
-    }
-}
-
-fn part1(claims: &Vec<Claim>) -> usize {
+fn part1(claims: &[Claim]) -> usize {
     let mut overlapping: HashSet<Point> = HashSet::new();
-    for (c, claim) in claims.into_iter().enumerate() {
-        for other in claims.into_iter().skip(c+1) {
+    for (c, claim) in claims.iter().enumerate() {
+        for other in claims.iter().skip(c+1) {
             if let Some(patch) = claim.patch.overlap(&other.patch) {
                 for point in patch {
                     overlapping.insert(point);
@@ -102,10 +83,10 @@ fn part1(claims: &Vec<Claim>) -> usize {
             }
         }
     }
-    return overlapping.into_iter().count();
+    overlapping.len()
 }
 
-fn part2(claims: &Vec<Claim>) -> u32 {
+fn part2(claims: &[Claim]) -> u32 {
     for claim in claims {
         let mut has_overlap = false;
         for other in claims {
@@ -129,16 +110,30 @@ fn parse(input: String) -> Vec<Claim> {
         result.push(Claim { 
             id: m[1].parse().unwrap(),
             patch: Patch {
-                point: Point::from_str(&m[2], &m[3]),
-                size: Point::from_str(&m[4], &m[5]),
+                point: Point::new([m[2].parse().unwrap(), m[3].parse().unwrap()]),
+                size: Point::new([m[4].parse().unwrap(), m[5].parse().unwrap()]),
             }
         });
     }
-    return result;
+    result
 }
 
-fn main() {
-    let input = parse(get_input(03, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day03;
+
+impl Solution for Day03 {
+    type Input = Vec<Claim>;
+    type Part1 = usize;
+    type Part2 = u32;
+
+    fn parse(text: String) -> Vec<Claim> {
+        parse(text)
+    }
+
+    fn part1(input: &Vec<Claim>) -> usize {
+        part1(input)
+    }
+
+    fn part2(input: &Vec<Claim>) -> u32 {
+        part2(input)
+    }
 }
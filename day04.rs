@@ -1,12 +1,10 @@
-extern crate common;
-extern crate regex;
-
 use std::cmp::Ordering;
 use std::collections::HashMap;
 
-use common::get_input;
 use regex::Regex;
 
+use common::Solution;
+
 type GuardId = u16;
 
 #[derive(Clone, Debug)]
@@ -31,7 +29,7 @@ impl EventType {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-struct Date {
+pub struct Date {
     year: u16,
     month: u8,
     day: u8,
@@ -41,7 +39,7 @@ struct Date {
 
 impl Date {
     fn new(year: &str, month: &str, day: &str, hour: &str, minute: &str) -> Date {
-        return Date {
+        Date {
             year: year.parse().unwrap(),
             month: month.parse().unwrap(),
             day: day.parse().unwrap(),
@@ -51,8 +49,8 @@ impl Date {
     }
 
     fn hour_diff(&self, other: &Date) -> u32 {
-        return (u32::from(other.hour) - u32::from(self.hour)) * 60 +
-            u32::from(other.minute) - u32::from(self.minute);
+        (u32::from(other.hour) - u32::from(self.hour)) * 60 +
+            u32::from(other.minute) - u32::from(self.minute)
     }
 }
 
@@ -64,7 +62,7 @@ struct Event {
 
 impl PartialOrd for Date {
     fn partial_cmp(&self, other: &Date) -> Option<Ordering> {
-        return Some(self.cmp(&other));
+        Some(self.cmp(other))
     }
 }
 
@@ -75,7 +73,7 @@ impl Ord for Date {
         if self.day != other.day { return self.day.cmp(&other.day) }
         if self.hour != other.hour { return self.hour.cmp(&other.hour) }
         if self.minute != other.minute { return self.minute.cmp(&other.minute) }
-        return Ordering::Equal
+        Ordering::Equal
     }
 }
 
@@ -85,9 +83,9 @@ fn part1(guard_events: &HashMap<GuardId, Vec<(Date, Date)>>) -> u32 {
         .map(|(guard, times)| {
             let mut total = 0;
             for (start, stop) in times {
-                total += start.hour_diff(&stop);
+                total += start.hour_diff(stop);
             }
-            return (guard, times, total);
+            (guard, times, total)
         })
         .max_by_key(|(_, _, time)| *time)
         .unwrap();
@@ -103,10 +101,10 @@ fn part1(guard_events: &HashMap<GuardId, Vec<(Date, Date)>>) -> u32 {
             }
         }
     }
-    return guard as u32 * (*minute_map
+    guard as u32 * (*minute_map
         .iter()
         .max_by_key(|(_, &v)| v)
-        .unwrap().0 as u32);
+        .unwrap().0 as u32)
 }
 
 fn part2(events: &HashMap<GuardId, Vec<(Date, Date)>>) -> u32 {
@@ -131,10 +129,10 @@ fn part2(events: &HashMap<GuardId, Vec<(Date, Date)>>) -> u32 {
         .iter()
         .max_by_key(|(_, &v)| v)
         .unwrap();
-    return (guard as u32) * (*minute as u32);
+    (guard as u32) * (*minute as u32)
 }
 
-fn parse<'a>(lines: String) -> HashMap<GuardId, Vec<(Date, Date)>> {
+fn parse(lines: String) -> HashMap<GuardId, Vec<(Date, Date)>> {
     let event = Regex::new(r"\[(\d{4})\-(\d{2})\-(\d{2}) (\d{2}):(\d{2})\] (.*)$").unwrap();
     let mut events: Vec<Event> = Vec::new();
     for line in lines.trim().split('\n') {
@@ -154,17 +152,31 @@ fn parse<'a>(lines: String) -> HashMap<GuardId, Vec<(Date, Date)>> {
             EventType::FallsAsleep => sleep_start = Some(event.date),
             EventType::WakesUp => {
                 result.entry(guard.unwrap())
-                   .or_insert(Vec::new())
+                   .or_default()
                    .push((sleep_start.unwrap(), event.date));
                 sleep_start = None;
             }
         }
     }
-    return result;
+    result
 }
 
-fn main() {
-    let input = parse(get_input(04, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day04;
+
+impl Solution for Day04 {
+    type Input = HashMap<GuardId, Vec<(Date, Date)>>;
+    type Part1 = u32;
+    type Part2 = u32;
+
+    fn parse(text: String) -> HashMap<GuardId, Vec<(Date, Date)>> {
+        parse(text)
+    }
+
+    fn part1(input: &HashMap<GuardId, Vec<(Date, Date)>>) -> u32 {
+        part1(input)
+    }
+
+    fn part2(input: &HashMap<GuardId, Vec<(Date, Date)>>) -> u32 {
+        part2(input)
+    }
 }
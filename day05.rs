@@ -1,10 +1,8 @@
-extern crate common;
-
 use std::collections::HashSet;
 
-use common::get_input;
+use common::Solution;
 
-fn part1(characters: &String) -> usize {
+fn part1(characters: &str) -> usize {
     let mut polymer = Vec::new();
     let mut next: Vec<char> = characters.chars().collect();
     while polymer != next {
@@ -12,8 +10,8 @@ fn part1(characters: &String) -> usize {
         next = Vec::new();
         for &head in &polymer {
             let tail = next.last();
-            if tail == None || 
-                head.to_ascii_uppercase() != tail.unwrap().to_ascii_uppercase() || 
+            if tail.is_none() || 
+                !head.eq_ignore_ascii_case(tail.unwrap()) || 
                     head == *tail.unwrap() {
                 next.push(head);
             } else {
@@ -21,10 +19,10 @@ fn part1(characters: &String) -> usize {
             }
         }
     }
-    return polymer.len();
+    polymer.len()
 }
 
-fn part2(characters: &String) -> usize {
+fn part2(characters: &str) -> usize {
     let chars: HashSet<char> = characters
         .chars()
         .map(|c| c.to_ascii_uppercase())
@@ -36,15 +34,29 @@ fn part2(characters: &String) -> usize {
             .filter(|c| c.to_ascii_uppercase() != r)
             .collect();
         let count = part1(&filtered);
-        if min_count == None || min_count.unwrap()  > count {
+        if min_count.is_none() || min_count.unwrap()  > count {
             min_count = Some(count);
         }
     }
-    return min_count.unwrap();
+    min_count.unwrap()
 }
 
-fn main() {
-    let input = get_input(05, 2018);
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day05;
+
+impl Solution for Day05 {
+    type Input = String;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(text: String) -> String {
+        text
+    }
+
+    fn part1(input: &String) -> usize {
+        part1(input)
+    }
+
+    fn part2(input: &String) -> usize {
+        part2(input)
+    }
 }
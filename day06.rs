@@ -1,61 +1,35 @@
-extern crate common;
-
-use common::get_input;
-
 use std::collections::{HashSet, HashMap};
 use std::iter::FromIterator;
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
-struct Point {
-    x: i32,
-    y: i32,
-}
-
-impl Point {
-    fn distance(&self, q: &Point) -> i32 {
-        (self.x - q.x).abs() + (self.y - q.y).abs()
-    }
-
-    fn add(&self, other: &Point) -> Point {
-        Point { x: self.x + other.x, y: self.y + other.y }
-    }
+use common::point::PointN;
+use common::Solution;
 
-    fn surrounding(&self) -> Vec<Point> {
-        vec![
-            Point { x: 0, y: 1 },
-            Point { x: 0, y: -1 },
-            Point { x: 1, y: 0 },
-            Point { x: -1, y: 0 }]
-            .iter()
-            .map(|p| self.add(p))
-            .collect()
-    }
+type Point = PointN<i32, 2>;
 
-    fn find_closest<'a>(&self, points: &'a Vec<Point>) -> Option<&'a Point> {
-        let mut points_iter = points.into_iter();
-        let mut result = Some(points_iter.next().unwrap());
-        let mut min_distance = self.distance(&result.unwrap());
-        for point in points_iter {
-            if self.distance(&point) < min_distance {
-                min_distance = self.distance(&point);
-                result = Some(point);
-            } else if self.distance(&point) == min_distance && result != None {
-                result = None;
-            }
+fn find_closest<'a>(from: &Point, points: &'a [Point]) -> Option<&'a Point> {
+    let mut points_iter = points.iter();
+    let mut result = Some(points_iter.next().unwrap());
+    let mut min_distance = from.distance(result.unwrap());
+    for point in points_iter {
+        if from.distance(point) < min_distance {
+            min_distance = from.distance(point);
+            result = Some(point);
+        } else if from.distance(point) == min_distance && result.is_some() {
+            result = None;
         }
-        return result;
     }
+    result
 }
 
-fn part1(points: &Vec<Point>) -> u32 {
+fn part1(points: &[Point]) -> u32 {
     let mut enclosed: HashMap<&Point, HashSet<Point>> = HashMap::new();
     let mut boundaries: HashMap<&Point, HashSet<Point>> = HashMap::new();
-    let mut max_d: Option<i32> = None;
+    let mut max_d: Option<usize> = None;
     for point in points {
         enclosed.insert(point, HashSet::new());
-        boundaries.insert(point, HashSet::from_iter(vec![point.clone()]));
+        boundaries.insert(point, HashSet::from_iter(vec![*point]));
         for q in points {
-            if max_d == None || point.distance(q) > max_d.unwrap() {
+            if max_d.is_none() || point.distance(q) > max_d.unwrap() {
                 max_d = Some(point.distance(q));
             }
         }
@@ -68,11 +42,11 @@ fn part1(points: &Vec<Point>) -> u32 {
                 if seen.contains(point) {
                     continue;
                 }
-                seen.insert(point.clone());
-                if let Some(closest) = point.find_closest(points) {
-                    enclosed.get_mut(closest).unwrap().insert(point.clone());
+                seen.insert(*point);
+                if let Some(closest) = find_closest(point, points) {
+                    enclosed.get_mut(closest).unwrap().insert(*point);
                 }
-                for neighbor in point.surrounding() {
+                for neighbor in point.neighbors() {
                     new_boundary.insert(neighbor);
                 }
             }
@@ -80,15 +54,15 @@ fn part1(points: &Vec<Point>) -> u32 {
         }
     }
     enclosed.iter()
-        .filter(|(&k, _)| boundaries.get(k).unwrap().len() == 0)
+        .filter(|(&k, _)| boundaries.get(k).unwrap().is_empty())
         .map(|(_, v)| v.len())
         .max()
         .unwrap() as u32
 }
 
-fn part2(records: &Vec<Point>) -> usize {
-    let limit: i32 = 10000;
-    let mut queue = records.clone();
+fn part2(records: &[Point]) -> usize {
+    let limit: usize = 10000;
+    let mut queue = records.to_vec();
     let mut seen: HashSet<Point> = HashSet::new();
     let mut area: HashSet<Point> = HashSet::new();
     while !queue.is_empty() {
@@ -96,19 +70,19 @@ fn part2(records: &Vec<Point>) -> usize {
         if seen.contains(&point) {
             continue;
         }
-        seen.insert(point.clone());
+        seen.insert(point);
 
-        let dist: i32 = records.iter().map(|p| point.distance(p)).sum();
+        let dist: usize = records.iter().map(|p| point.distance(p)).sum();
         if limit <= dist {
             continue;
         }
-        area.insert(point.clone());
+        area.insert(point);
 
-        for neighbor in point.surrounding() {
+        for neighbor in point.neighbors() {
             queue.push(neighbor);
         }
     }
-    return area.len();
+    area.len()
 }
 
 fn parse(lines: String) -> Vec<Point> {
@@ -118,13 +92,27 @@ fn parse(lines: String) -> Vec<Point> {
             .split(", ")
             .map(|v| v.parse::<i32>().unwrap())
             .collect();
-        result.push(Point { x:  items[0], y: items[1] });
+        result.push(Point::new([items[0], items[1]]));
     }
-    return result;
+    result
 }
 
-fn main() {
-    let input = parse(get_input(06, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day06;
+
+impl Solution for Day06 {
+    type Input = Vec<Point>;
+    type Part1 = u32;
+    type Part2 = usize;
+
+    fn parse(text: String) -> Vec<Point> {
+        parse(text)
+    }
+
+    fn part1(input: &Vec<Point>) -> u32 {
+        part1(input)
+    }
+
+    fn part2(input: &Vec<Point>) -> usize {
+        part2(input)
+    }
 }
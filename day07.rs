@@ -1,10 +1,9 @@
-extern crate common;
-
-use common::get_input;
 use regex::Regex;
 
 use std::collections::{HashMap, HashSet};
 
+use common::Solution;
+
 type Input = HashMap<String, HashSet<String>>;
 
 fn part1(input: &Input) -> String {
@@ -21,7 +20,7 @@ fn part1(input: &Input) -> String {
         todo.remove(&to_remove[0]);
         result.push(to_remove[0].clone());
     }
-    return result.join("");
+    result.join("")
 }
 
 fn part2(input: &Input) -> u32 {
@@ -53,13 +52,13 @@ fn part2(input: &Input) -> u32 {
         while workers.len() < 5 && !ready.is_empty() {
             let item = ready.remove(0);
             let first = item.chars().next().unwrap() as u8;
-            workers.push(((first - 'A' as u8 + 60) as u32, item.clone()));
+            workers.push(((first - b'A' + 60) as u32, item.clone()));
         }
     }
     time
 }
 
-fn parse(lines: &String) -> Input {
+fn parse(lines: String) -> Input {
     let mut result = HashMap::new();
     let format = Regex::new("Step (.) must be finished before step (.) can begin.")
         .unwrap();
@@ -70,11 +69,25 @@ fn parse(lines: &String) -> Input {
             .or_insert(HashSet::new())
             .insert(groups[1].to_string());
     }
-    return result
+    result
 }
 
-fn main() {
-    let input = parse(&get_input(07, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day07;
+
+impl Solution for Day07 {
+    type Input = Input;
+    type Part1 = String;
+    type Part2 = u32;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> String {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> u32 {
+        part2(input)
+    }
 }
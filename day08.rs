@@ -1,17 +1,15 @@
-extern crate common;
-
-use common::get_input;
+use common::Solution;
 
 type Input = Node;
 
 #[derive(Debug)]
-struct Node {
+pub struct Node {
     meta: Vec<u32>,
     children: Vec<Node>,
 }
 
 impl Node {
-    fn new(values: &mut Iterator<Item=u32>) -> Node {
+    fn new(values: &mut dyn Iterator<Item=u32>) -> Node {
         let n_children = values.next().unwrap();
         let n_meta = values.next().unwrap();
         let mut root = Node { meta: Vec::new(), children: Vec::new() };
@@ -21,13 +19,13 @@ impl Node {
         for _ in 0..n_meta {
             root.meta.push(values.next().unwrap());
         }
-        return root;
+        root
     }
 
     fn sum_meta(&self) -> u32 {
         let child_sum: u32 = self.children.iter().map(|v| v.sum_meta()).sum();
         let meta_sum: u32 = self.meta.iter().sum();
-        return meta_sum + child_sum;
+        meta_sum + child_sum
     }
 
     fn value(&self) -> u32 {
@@ -40,7 +38,7 @@ impl Node {
                 total += child.value();
             }
         }
-        return total
+        total
     }
 }
 
@@ -55,11 +53,25 @@ fn part2(input: &Input) -> u32 {
 fn parse(input: String) -> Input {
     let mut values = input.trim().split(" ")
         .map(|v| v.parse().unwrap());
-    return Node::new(&mut values)
+    Node::new(&mut values)
 }
 
-fn main() {
-    let input = parse(get_input(08, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day08;
+
+impl Solution for Day08 {
+    type Input = Input;
+    type Part1 = u32;
+    type Part2 = u32;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> u32 {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> u32 {
+        part2(input)
+    }
 }
@@ -1,11 +1,10 @@
-extern crate common;
-
-use common::get_input;
 use regex::Regex;
 
 use std::collections::VecDeque;
 
-struct Input {
+use common::Solution;
+
+pub struct Input {
     n_players: usize,
     n_marbles: usize,
 }
@@ -63,8 +62,22 @@ fn parse(input: String) -> Input {
     }
 }
 
-fn main() {
-    let input = parse(get_input(09, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day09;
+
+impl Solution for Day09 {
+    type Input = Input;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> usize {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> usize {
+        part2(input)
+    }
 }
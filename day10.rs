@@ -1,13 +1,15 @@
-extern crate common;
-
 use regex::Regex;
-use std::ops::Add;
+use std::fmt;
 use std::collections::HashSet;
 
+use common::point::PointN;
+use common::Solution;
+
 type Input = Vec<Point>;
+type Point2d = PointN<isize, 2>;
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
-struct Point {
+pub struct Point {
     position: Point2d,
     velocity: Point2d,
 }
@@ -15,28 +17,12 @@ struct Point {
 impl Point {
     fn step(&self) -> Point {
          Point {
-             position: &self.position + &self.velocity,
-             velocity: self.velocity.clone()
+             position: self.position + self.velocity,
+             velocity: self.velocity
          }
     }
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
-struct Point2d {
-    x: isize,
-    y: isize,
-}
-
-impl Add for &Point2d {
-    type Output = Point2d;
-    fn add(self, other: &Point2d) -> Point2d {
-        Point2d {
-            x: self.x + other.x,
-            y: self.y + other.y,
-        }
-    }
-}
-
 #[derive(Debug, Clone)]
 struct PointCloud {
     points: HashSet<Point>
@@ -46,24 +32,21 @@ impl PointCloud {
     fn new<'a, I>(items: I) -> PointCloud
         where I: Iterator<Item=&'a Point> {
         PointCloud {
-            points: items.map(|p| p.clone()).collect()
+            points: items.cloned().collect()
         }
     }
 
     fn limits(&self) -> (Point2d, Point2d) {
-        (Point2d {
-            x: self.points.iter().map(|p| p.position.x).min().unwrap(),
-            y: self.points.iter().map(|p| p.position.y).min().unwrap(),
-        },
-        Point2d {
-            x: self.points.iter().map(|p| p.position.x).max().unwrap(),
-            y: self.points.iter().map(|p| p.position.y).max().unwrap(),
-        })
+        let xs = || self.points.iter().map(|p| p.position.x());
+        let ys = || self.points.iter().map(|p| p.position.y());
+        (Point2d::new([xs().min().unwrap(), ys().min().unwrap()]),
+         Point2d::new([xs().max().unwrap(), ys().max().unwrap()]))
     }
 
     fn area(&self) -> usize {
         let (min, max) = self.limits();
-        ((max.x - min.x) * (max.y - min.y)) as usize
+        let size = max - min;
+        (size.x() * size.y()) as usize
     }
 
     fn next(&self) -> PointCloud {
@@ -76,20 +59,23 @@ impl PointCloud {
 }
 
 
-impl ToString for PointCloud {
-    fn to_string(&self) -> String {
+impl fmt::Display for PointCloud {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut output: Vec<Vec<&str>> = Vec::new();
         let (min, max) = self.limits();
-        let positions: HashSet<(isize, isize)> = self.points.iter().map(|p| (p.position.x, p.position.y)).collect();
-        for r in min.y..max.y+1 {
+        let positions: HashSet<Point2d> = self.points.iter().map(|p| p.position).collect();
+        for r in min.y()..max.y()+1 {
             let mut row = Vec::new();
-            for c in min.x..max.x+1 {
-                let this_char = if positions.contains(&(c, r)) { "#" } else { " " };
+            for c in min.x()..max.x()+1 {
+                let this_char = if positions.contains(&Point2d::new([c, r])) { "#" } else { " " };
                 row.push(this_char);
             }
             output.push(row);
         }
-        return output.iter().map(|row| format!("\n{}", row.join(""))).collect::<String>()
+        for row in output {
+            write!(f, "\n{}", row.join(""))?;
+        }
+        Ok(())
     }
 }
 
@@ -126,20 +112,34 @@ fn parse(input: String) -> Input {
     input.trim().lines().map(|line| {
         let groups = regex.captures(line.trim()).unwrap();
         Point {
-            position: Point2d {
-                x: groups[1].trim().parse().unwrap(),
-                y: groups[2].trim().parse().unwrap(),
-            },
-            velocity: Point2d {
-                x: groups[3].trim().parse().unwrap(),
-                y: groups[4].trim().parse().unwrap(),
-            },
+            position: Point2d::new([
+                groups[1].trim().parse().unwrap(),
+                groups[2].trim().parse().unwrap(),
+            ]),
+            velocity: Point2d::new([
+                groups[3].trim().parse().unwrap(),
+                groups[4].trim().parse().unwrap(),
+            ]),
         }
     }).collect()
 }
 
-fn main() {
-    let input = parse(common::get_input(10, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day10;
+
+impl Solution for Day10 {
+    type Input = Input;
+    type Part1 = String;
+    type Part2 = usize;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> String {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> usize {
+        part2(input)
+    }
 }
@@ -1,7 +1,7 @@
-extern crate common;
-
 use std::cmp::min;
 
+use common::Solution;
+
 type Input = isize;
 
 fn make_cells(size: usize, input: isize) -> Vec<Vec<isize>> {
@@ -25,7 +25,7 @@ fn make_cells(size: usize, input: isize) -> Vec<Vec<isize>> {
     cells
 }
 
-fn value(cells: &Vec<Vec<isize>>, size: (usize, usize, usize)) -> isize {
+fn value(cells: &[Vec<isize>], size: (usize, usize, usize)) -> isize {
     let (x, y, z) = size;
     let mut this = cells[y+z+1][x+z+1];
     if y > 0 {
@@ -40,6 +40,10 @@ fn value(cells: &Vec<Vec<isize>>, size: (usize, usize, usize)) -> isize {
     this
 }
 
+fn parse(input: String) -> Input {
+    input.trim().parse::<Input>().unwrap()
+}
+
 fn part1(input: &Input) -> String {
     let cells = make_cells(300, *input);
     let mut max: Option<isize> = None;
@@ -47,7 +51,7 @@ fn part1(input: &Input) -> String {
     for y in 0..(300 - 3) {
         for x in 0..(300 - 3) {
             let this = value(&cells, (x, y, 3-1));
-            if max == None || max.unwrap() < this {
+            if max.is_none() || max.unwrap() < this {
                 max = Some(this);
                 upper_left = Some(format!("{},{}", x+2, y+2).to_string());
             }
@@ -64,7 +68,7 @@ fn part2(input: &Input) -> String {
         for x in 0..300 {
             for z in 0..min(300-x-1, 300-y-1) {
                 let this = value(&cells, (x, y, z));
-                if max == None || max.unwrap() < this {
+                if max.is_none() || max.unwrap() < this {
                     max = Some(this);
                     square = Some(format!("{},{},{}", x+2, y+2, z+1));
                 }
@@ -74,8 +78,22 @@ fn part2(input: &Input) -> String {
     square.unwrap()
 }
 
-fn main() {
-    let input = common::get_input(11, 2018).trim().parse::<Input>().unwrap();
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day11;
+
+impl Solution for Day11 {
+    type Input = Input;
+    type Part1 = String;
+    type Part2 = String;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> String {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> String {
+        part2(input)
+    }
 }
@@ -1,41 +1,41 @@
-extern crate common;
-
 use std::collections::HashMap;
+use std::fmt;
 use regex::Regex;
 
+use common::cycle;
+use common::Solution;
+
 type Pot = char;
 
 #[derive(Clone)]
-struct Pots {
+pub struct Pots {
     mapping: HashMap<String, char>,
     slots: HashMap<isize, Pot>
 }
 
 impl Pots {
-    fn new(pots: &mut Iterator<Item=char>, mapping: HashMap<String, char>) -> Pots {
+    fn new(pots: &mut dyn Iterator<Item=char>, mapping: HashMap<String, char>) -> Pots {
         let mut slots = HashMap::new();
         for (i, pot) in pots.enumerate() {
             slots.insert(i as isize, pot);
         }
-        Pots { slots: slots, mapping: mapping }
+        Pots { slots, mapping }
     }
 
     fn min(&self) -> isize {
-        *self.slots.keys().filter(|i| {
-            match self.slots.get(&i) {
-                Some('#') => true,
-                _ => false
-            }
-        }).min().unwrap()
+        *self.slots.keys().filter(|i| matches!(self.slots.get(i), Some('#')))
+            .min().unwrap()
     }
 
     fn max(&self) -> isize {
-        *self.slots.keys().filter(|i| {
-            match self.slots.get(&i) {
-                Some('#') => true,
-                _ => false
-            }
-        }).max().unwrap()
+        *self.slots.keys().filter(|i| matches!(self.slots.get(i), Some('#')))
+            .max().unwrap()
+    }
+
+    fn planted(&self) -> Vec<isize> {
+        (self.min()..self.max()+1)
+            .filter(|i| matches!(self.slots.get(i), Some('#')))
+            .collect()
     }
 }
 
@@ -49,9 +49,11 @@ impl Pots {
     }
 }
 
-impl ToString for Pots {
-    fn to_string(&self) -> String {
-        (self.min()..self.max()+1).map(|i| self.slots.get(&i).unwrap_or(&'.')).collect()
+impl fmt::Display for Pots {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pots: String = (self.min()..self.max()+1)
+            .map(|i| self.slots.get(&i).unwrap_or(&'.')).collect();
+        write!(f, "{}", pots)
     }
 }
 
@@ -60,29 +62,25 @@ fn part1(start: &Pots) -> isize {
     for _ in 0..20 {
         pots.next();
     }
-    (pots.min()..pots.max()+1).filter_map(|i| {
-        match pots.slots.get(&i) {
-            Some('#') => Some(i),
-            _ => None
-        }
-    }).sum()
+    pots.planted().iter().sum()
 }
 
 fn part2(start: &Pots) -> isize {
-    let mut pots = start.clone();
-    let mut countdown: usize = 50000000000;
-    let mut prev: Option<String> = None;
-    while countdown > 0 && prev != Some(pots.to_string()) {
-        prev = Some(pots.to_string());
-        pots.next();
-        countdown -= 1;
-    }
-    (pots.min()..pots.max()+1).filter_map(|i| {
-        match pots.slots.get(&i) {
-            Some('#') => Some(i + countdown as isize),
-            _ => None
-        }
-    }).sum()
+    let generations: usize = 50000000000;
+    let grow = |pots: &Pots| {
+        let mut next = pots.clone();
+        next.next();
+        next
+    };
+    let history = cycle::detect_by(start.clone(), grow, |pots| pots.to_string());
+    let pattern = history.cycle.expect("Pots never settle");
+    let equivalent = pattern.equivalent(generations);
+    // Each loop repeats the same pattern, but it may have drifted along the row
+    let first = &history.states[pattern.start];
+    let drift = grow(history.states.last().unwrap()).min() - first.min();
+    let loops = ((generations - equivalent) / pattern.length) as isize;
+    let planted = history.states[equivalent].planted();
+    planted.iter().sum::<isize>() + planted.len() as isize * drift * loops
 }
 
 fn parse(input: String) -> Pots {
@@ -104,8 +102,22 @@ fn parse(input: String) -> Pots {
     Pots::new(&mut pots_line[1].chars(), mapping)
 }
 
-fn main() {
-    let pots = parse(common::get_input(12, 2018));
-    println!("Part 1: {}", part1(&pots));
-    println!("Part 2: {}", part2(&pots));
+pub struct Day12;
+
+impl Solution for Day12 {
+    type Input = Pots;
+    type Part1 = isize;
+    type Part2 = isize;
+
+    fn parse(text: String) -> Pots {
+        parse(text)
+    }
+
+    fn part1(input: &Pots) -> isize {
+        part1(input)
+    }
+
+    fn part2(input: &Pots) -> isize {
+        part2(input)
+    }
 }
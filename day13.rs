@@ -1,10 +1,12 @@
-use common::get_input;
-
 use std::collections::{HashMap, HashSet};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::fmt;
-use std::ops::Add;
+
+use common::point::PointN;
+use common::Solution;
+
+pub type Point = PointN<isize, 2>;
 
 macro_rules! map(
     { $($key:tt : $value:expr),+ } => {
@@ -18,28 +20,8 @@ macro_rules! map(
     };
 );
 
-#[derive(Hash, Eq, PartialEq, Clone)]
-struct Point {
-    x: isize,
-    y: isize,
-}
-
-impl fmt::Debug for Point {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({}, {})", self.x, self.y)
-    }
-}
-
-impl Add for &Point {
-    type Output = Point;
-
-    fn add(self, other: &Point) -> Point {
-        Point { x: self.x + other.x, y: self.y + other.y }
-    }
-}
-
 #[derive(Clone, PartialEq, Hash, Eq)]
-struct Cart {
+pub struct Cart {
     location: Point,
     heading: Point,
     turns: usize,
@@ -49,10 +31,10 @@ struct Cart {
 impl fmt::Debug for Cart {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut chars = HashMap::new();
-        chars.insert(Point { x: -1, y: 0 }, '<');
-        chars.insert(Point { x: 1, y: 0 }, '>');
-        chars.insert(Point { x: 0, y: -1 }, '^');
-        chars.insert(Point { x: 0, y: 1 }, 'v');
+        chars.insert(Point::new([-1, 0]), '<');
+        chars.insert(Point::new([1, 0]), '>');
+        chars.insert(Point::new([0, -1]), '^');
+        chars.insert(Point::new([0, 1]), 'v');
         if self.active {
             write!(f, "Cart({}, {:?})", chars.get(&self.heading).unwrap(), self.location)
         } else {
@@ -63,25 +45,19 @@ impl fmt::Debug for Cart {
 
 impl Cart {
     fn turn_left(&mut self) {
-        self.heading = Point {
-            x: self.heading.y,
-            y: -self.heading.x,
-        };
+        self.heading = Point::new([self.heading.y(), -self.heading.x()]);
     }
 
     fn turn_right(&mut self) {
-        self.heading = Point {
-            x: -self.heading.y,
-            y: self.heading.x,
-        };
+        self.heading = Point::new([-self.heading.y(), self.heading.x()]);
     }
 
     fn move_forward(&mut self) {
-        self.location = &self.location + &self.heading;
+        self.location = self.location + self.heading;
     }
 
     fn turn(&mut self, track: &char) {
-        match (track, (self.heading.x, self.heading.y)) {
+        match (track, (self.heading.x(), self.heading.y())) {
             ('/', (0, -1)) => self.turn_right(),
             ('/', (-1, 0)) => self.turn_left(),
             ('/', (0, 1)) => self.turn_right(),
@@ -106,6 +82,8 @@ impl Cart {
 
 type CartRef = Rc<RefCell<Cart>>;
 
+type Input = (HashMap<Point, char>, Vec<Cart>);
+
 #[derive(Clone)]
 struct Track {
     track: HashMap<Point, char>,
@@ -120,7 +98,7 @@ impl Track {
                 carts.push(cart.clone());
             } else {
                 collisions.insert(
-                    cart.borrow().location.clone(),
+                    cart.borrow().location,
                     vec![cart.clone()]
                 );
             }
@@ -133,7 +111,7 @@ impl Track {
                 carts.push(cart.clone());
             } else {
                 collisions.insert(
-                    cart.borrow().location.clone(),
+                    cart.borrow().location,
                     vec![cart.clone()]
                 );
             }
@@ -152,7 +130,7 @@ impl Track {
     }
 }
 
-fn part1(track: &HashMap<Point, char>, carts: &Vec<Cart>) -> String {
+fn part1(track: &HashMap<Point, char>, carts: &[Cart]) -> String {
     let mut state = Track {
         track: track.clone(),
         carts: carts.iter().map(|c| Rc::new(RefCell::new(c.clone()))).collect(),
@@ -160,12 +138,12 @@ fn part1(track: &HashMap<Point, char>, carts: &Vec<Cart>) -> String {
     loop {
         let collisions = state.step();
         if let Some(p) = collisions.iter().next() {
-            return format!("{},{}", p.x, p.y)
+            return format!("{},{}", p.x(), p.y())
         }
     }
 }
 
-fn part2(track: &HashMap<Point, char>, carts: &Vec<Cart>) -> String {
+fn part2(track: &HashMap<Point, char>, carts: &[Cart]) -> String {
     let mut state = Track {
         track: track.clone(),
         carts: carts.iter().map(|c| Rc::new(RefCell::new(c.clone()))).collect(),
@@ -173,42 +151,57 @@ fn part2(track: &HashMap<Point, char>, carts: &Vec<Cart>) -> String {
     while state.carts.len() > 1 {
         state.step();
     }
-    if let Some(p) = state.carts.iter().next() {
-        format!("{},{}", p.borrow().location.x, p.borrow().location.y)
+    if let Some(p) = state.carts.first() {
+        let location = p.borrow().location;
+        format!("{},{}", location.x(), location.y())
     } else {
         panic!("Woops");
     }
 }
 
-fn parse(lines: String) -> (HashMap<Point, char>, Vec<Cart>) {
+fn parse(lines: String) -> Input {
     let mut carts: Vec<Cart> = Vec::new();
     let mut track: HashMap<Point, char> = HashMap::new();
     let cart_map = map!{
-        '>': ('-', Point { x: 1, y: 0 }),
-        '<': ('-', Point { x: -1, y: 0}),
-        'v': ('|', Point { x: 0, y: 1}),
-        '^': ('|', Point { x: 0, y: -1})
+        '>': ('-', Point::new([1, 0])),
+        '<': ('-', Point::new([-1, 0])),
+        'v': ('|', Point::new([0, 1])),
+        '^': ('|', Point::new([0, -1]))
     };
     for (r, line) in lines.split('\n').enumerate() {
         for (c, segment) in line.chars().enumerate() {
             if let Some((segment, heading)) = cart_map.get(&segment) {
-                track.insert(Point { x: c as isize, y: r as isize }, *segment);
+                track.insert(Point::new([c as isize, r as isize]), *segment);
                 carts.push(Cart { 
-                    location: Point { x: c as isize, y: r as isize },
-                    heading: heading.clone(),
+                    location: Point::new([c as isize, r as isize]),
+                    heading: *heading,
                     turns: 0,
                     active: true,
                 });
             } else if segment != ' '{
-                track.insert(Point { x: c as isize, y: r as isize }, segment);
+                track.insert(Point::new([c as isize, r as isize]), segment);
             }
         }
     }
     (track, carts)
 }
 
-fn main() {
-    let (track, carts) = parse(get_input(13, 2018));
-    println!("Part 1: {}", part1(&track, &carts));
-    println!("Part 2: {}", part2(&track, &carts));
+pub struct Day13;
+
+impl Solution for Day13 {
+    type Input = Input;
+    type Part1 = String;
+    type Part2 = String;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> String {
+        part1(&input.0, &input.1)
+    }
+
+    fn part2(input: &Input) -> String {
+        part2(&input.0, &input.1)
+    }
 }
@@ -1,4 +1,4 @@
-use common::get_input;
+use common::Solution;
 
 fn digits(number: u32) -> Vec<u8> {
     if number == 0 {
@@ -55,8 +55,22 @@ fn parse(input: String) -> u32 {
     input.trim().parse::<u32>().unwrap()
 }
 
-fn main() {
-    let input = parse(get_input(14, 2018));
-    println!("Part 1: {}", part1(input));
-    println!("Part 2: {}", part2(input));
+pub struct Day14;
+
+impl Solution for Day14 {
+    type Input = u32;
+    type Part1 = String;
+    type Part2 = String;
+
+    fn parse(text: String) -> u32 {
+        parse(text)
+    }
+
+    fn part1(input: &u32) -> String {
+        part1(*input)
+    }
+
+    fn part2(input: &u32) -> String {
+        part2(*input)
+    }
 }
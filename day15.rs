@@ -1,350 +1,602 @@
-use common::get_input;
-use std::collections::{HashMap, VecDeque, HashSet};
-use std::cmp::Ordering;
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::fmt::{self, Write};
-use std::ops::Add;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-struct Point {
-    x: i16,
-    y: i16,
+use regex::Regex;
+
+use common::grid::{Grid, Pos};
+use common::search;
+use common::Solution;
+
+#[derive(Clone, PartialEq, Eq)]
+struct Unit {
+    position: Pos,
+    race: char, 
+    hp: i16,
+    ap: i16,
+    start: Pos,
+    kills: u32,
+    /// Hit points taken from enemies, not counting overkill.
+    damage: u32,
 }
 
-impl fmt::Debug for Point {
+impl fmt::Debug for Unit {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Point(x={}, y={})", self.x, self.y)
+        match self.race {
+            'E' => write!(f, "Elf({:?}, attack={}, hp={})", 
+                          self.position, self.ap, self.hp),
+            'G' => write!(f, "Goblin({:?}, attack={}, hp={})", 
+                          self.position, self.ap, self.hp),
+            race => write!(f, "{}({:?}, attack={}, hp={})",
+                           race, self.position, self.ap, self.hp),
+        }
     }
 }
 
-impl Add for Point {
-    type Output = Point;
+impl Unit {
+    fn new(position: Pos, race: char, hp: i16, ap: i16) -> Unit {
+        Unit { position, race, hp, ap, start: position, kills: 0, damage: 0 }
+    }
 
-    fn add(self, other: Point) -> Point {
-        Point::new(self.x + other.x, self.y + other.y)
+    fn dead(&self) -> bool {
+        self.hp <= 0
     }
 }
 
-impl Point {
-    fn new(x: i16, y: i16) -> Point {
-        Point {x, y}
-    }
+type Input = Grid<char>;
 
-    fn surrounding(&self) -> Vec<Point> {
-        vec![
-            *self + Point::new(0, -1),
-            *self + Point::new(-1, 0),
-            *self + Point::new(1, 0),
-            *self + Point::new(0, 1),
-        ]
-    }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stats {
+    hp: i16,
+    ap: i16,
 }
 
-impl Ord for Point {
-    fn cmp(&self, other: &Point) -> Ordering {
-        match self.y.cmp(&other.y) {
-            Ordering::Less => Ordering::Less,
-            Ordering::Equal => self.x.cmp(&other.x),
-            Ordering::Greater => Ordering::Greater,
+/// Everything about a battle that is not on the map.
+#[derive(Clone, Debug)]
+struct CombatRules {
+    /// The map character of each race and the stats its units start with.
+    races: BTreeMap<char, Stats>,
+    /// Units may also move and attack diagonally.
+    diagonal: bool,
+    /// Groups of races that do not fight each other.
+    alliances: Vec<Vec<char>>,
+    /// Races whose first death ends the battle early.
+    abort_on_death: Vec<char>,
+}
+
+impl Default for CombatRules {
+    fn default() -> CombatRules {
+        let stats = Stats { hp: 200, ap: 3 };
+        CombatRules {
+            races: BTreeMap::from([('E', stats), ('G', stats)]),
+            diagonal: false,
+            alliances: Vec::new(),
+            abort_on_death: Vec::new(),
         }
     }
 }
 
-impl PartialOrd for Point {
-    fn partial_cmp(&self, other: &Point) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl CombatRules {
+    fn with_attack(mut self, race: char, ap: i16) -> CombatRules {
+        self.races.entry(race).or_insert(Stats { hp: 200, ap }).ap = ap;
+        self
+    }
+
+    fn allied(&self, a: char, b: char) -> bool {
+        a == b || self.alliances.iter().any(|group| group.contains(&a) && group.contains(&b))
     }
 }
 
-type UnitCell = Rc<RefCell<Unit>>;
+/// How a battle ended, unit by unit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BattleOutcome {
+    /// Races still standing, unless the battle was aborted.
+    winners: Vec<char>,
+    /// Full rounds completed.
+    rounds: u32,
+    /// Turns taken by all units, including the unfinished last round.
+    turns: u32,
+    /// Hit points left across all surviving units.
+    hp: u32,
+    /// Units lost, by race.
+    deaths: BTreeMap<char, usize>,
+    /// Ended by `CombatRules::abort_on_death` rather than fought out.
+    aborted: bool,
+    /// Every unit in the order they were read from the map, dead or alive.
+    units: Vec<Unit>,
+}
 
-struct Unit {
-    position: Point,
-    race: char, 
-    hp: i16,
-    ap: i16,
+fn race_name(race: char) -> String {
+    match race {
+        'E' => "Elves".to_string(),
+        'G' => "Goblins".to_string(),
+        race => race.to_string(),
+    }
 }
 
-impl fmt::Debug for Unit {
+impl BattleOutcome {
+    fn score(&self) -> u32 {
+        self.rounds * self.hp
+    }
+
+    pub fn to_json(&self) -> String {
+        let race = |race: char| json_string(&race.to_string());
+        let point = |p: Pos| format!("[{},{}]", p.x(), p.y());
+        let winners: Vec<String> = self.winners.iter().map(|&w| race(w)).collect();
+        let deaths: Vec<String> = self.deaths.iter().map(|(&r, n)| format!("{}:{}", race(r), n)).collect();
+        let units: Vec<String> = self.units.iter().map(|u| format!(
+            "{{\"race\":{},\"start\":{},\"end\":{},\"hp\":{},\"kills\":{},\"damage\":{}}}",
+            race(u.race), point(u.start), point(u.position), u.hp.max(0), u.kills, u.damage)).collect();
+        format!("{{\"winners\":[{}],\"rounds\":{},\"turns\":{},\"hp\":{},\"score\":{},\"aborted\":{},\"deaths\":{{{}}},\"units\":[{}]}}",
+                winners.join(","), self.rounds, self.turns, self.hp, self.score(), self.aborted,
+                deaths.join(","), units.join(","))
+    }
+}
+
+impl fmt::Display for BattleOutcome {
+    /// A summary line followed by a table of every unit.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.race {
-            'E' => write!(f, "Elf({:?}, attack={}, hp={})", 
-                          self.position, self.ap, self.hp),
-            'G' => write!(f, "Goblin({:?}, attack={}, hp={})", 
-                          self.position, self.ap, self.hp),
-            _ => panic!("Not valid"),
+        let names: Vec<String> = self.winners.iter().map(|&w| race_name(w)).collect();
+        match self.aborted {
+            true => write!(f, "Aborted")?,
+            false => write!(f, "{} won", names.join(" and "))?,
+        }
+        writeln!(f, " after {} rounds and {} turns with {} hit points left, outcome {}",
+                 self.rounds, self.turns, self.hp, self.score())?;
+        write!(f, "Race  Start     End       HP    Kills  Damage")?;
+        let point = |p: Pos| format!("{},{}", p.x(), p.y());
+        for unit in &self.units {
+            let hp = if unit.dead() { "dead".to_string() } else { unit.hp.to_string() };
+            write!(f, "\n{:<5} {:<9} {:<9} {:>4} {:>8} {:>7}",
+                   unit.race, point(unit.start), point(unit.position), hp, unit.kills, unit.damage)?;
         }
+        Ok(())
     }
 }
 
-impl Unit {
-    fn new(position: Point, race: char, hp: i16, ap: i16) -> Unit {
-        Unit { position, race, hp, ap }
+/// The cave, with units indexed by the cell they stand on. Dead units stay
+/// in `units` but leave the index.
+struct Board {
+    cave: Grid<char>,
+    occupied: Grid<Option<usize>>,
+    units: Vec<Unit>,
+    rounds: u32, 
+    rules: CombatRules,
+    /// Offsets to adjacent cells, in reading order.
+    offsets: Vec<Pos>,
+    turns: u32,
+    aborted: bool,
+}
+
+impl fmt::Debug for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.frame())
     }
+}
 
-    fn dead(&self) -> bool {
-        self.hp <= 0
+/// A snapshot of the board between rounds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    round: u32,
+    rows: Vec<String>,
+    /// Race, position and hit points of each unit, in reading order.
+    units: Vec<(char, Pos, i16)>,
+}
+
+fn json_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl Frame {
+    fn to_json(&self) -> String {
+        let rows: Vec<String> = self.rows.iter().map(|row| json_string(row)).collect();
+        let units: Vec<String> = self.units.iter()
+            .map(|(race, p, hp)| format!("{{\"race\":{},\"x\":{},\"y\":{},\"hp\":{}}}",
+                                         json_string(&race.to_string()), p.x(), p.y(), hp))
+            .collect();
+        format!("{{\"round\":{},\"rows\":[{}],\"units\":[{}]}}", self.round, rows.join(","), units.join(","))
     }
 }
 
-type Input = HashMap<Point, char>;
+fn json_unescape(text: &str) -> String {
+    let mut chars = text.chars();
+    let mut out = String::new();
+    while let Some(c) = chars.next() {
+        out.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
+    }
+    out
+}
 
-enum BoardSpace {
-    Wall,
-    Space,
-    Unit(UnitCell)
+/// Reads frames written by `Frame::to_json`, compiling its patterns once.
+struct FrameReader {
+    frame: Regex,
+    string: Regex,
+    unit: Regex,
+    units: Regex,
 }
 
-struct Board {
-    units: Vec<UnitCell>,
-    map: HashMap<Point, char>,
-    rounds: u32, 
+impl FrameReader {
+    fn new() -> FrameReader {
+        let string = r#""((?:[^"\\]|\\.)*)""#;
+        let unit = format!(r#"\{{"race":{},"x":(-?\d+),"y":(-?\d+),"hp":(-?\d+)\}}"#, string);
+        FrameReader {
+            frame: Regex::new(r#"^\{"round":(\d+),"rows":\[(.*)\],"units":\[(.*)\]\}$"#).unwrap(),
+            string: Regex::new(string).unwrap(),
+            units: Regex::new(&format!("^(?:{0}(?:,{0})*)?$", unit)).unwrap(),
+            unit: Regex::new(&unit).unwrap(),
+        }
+    }
+
+    fn read(&self, line: &str) -> Result<Frame, String> {
+        let invalid = || format!("Invalid frame {:?}", line);
+        let cap = self.frame.captures(line.trim()).ok_or_else(invalid)?;
+        if !self.units.is_match(&cap[3]) {
+            return Err(invalid());
+        }
+        let number = |text: &str| text.parse().map_err(|_| invalid());
+        let rows = self.string.captures_iter(&cap[2]).map(|row| json_unescape(&row[1])).collect();
+        let mut units = Vec::new();
+        for unit in self.unit.captures_iter(&cap[3]) {
+            let race: Vec<char> = json_unescape(&unit[1]).chars().collect();
+            let race = match race[..] {
+                [race] => race,
+                _ => return Err(invalid()),
+            };
+            let position = Pos::new([number(&unit[2])?, number(&unit[3])?]);
+            let hp = unit[4].parse().map_err(|_| invalid())?;
+            units.push((race, position, hp));
+        }
+        let round = cap[1].parse().map_err(|_| invalid())?;
+        Ok(Frame { round, rows, units })
+    }
 }
 
-impl fmt::Debug for Board {
+impl fmt::Display for Frame {
+    /// The board as in the puzzle, with the hit points of the units on
+    /// each row beside it.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut result = format!("Round {}/{}\n", self.rounds, 3);
-        let mut keys: Vec<&Point> = self.map.keys().collect();
-        keys.sort();
-        let mut curr_row = 0;
-        for key in keys {
-            if key.y > curr_row {
-                curr_row = key.y;
-                result.push('\n');
-            }
-            result.push(match self.get(key) {
-                BoardSpace::Unit(u) => u.borrow().race,
-                BoardSpace::Space => '.',
-                BoardSpace::Wall => '#',
-            });
+        write!(f, "Round {}", self.round)?;
+        for (y, row) in self.rows.iter().enumerate() {
+            let units: Vec<String> = self.units.iter()
+                .filter(|(_, p, _)| p.y() as usize == y)
+                .map(|(race, _, hp)| format!("{}({})", race, hp))
+                .collect();
+            write!(f, "\n{}   {}", row, units.join(", "))?;
         }
-        let mut units = self.units.clone();
-        units.sort_by_key(|u| u.borrow().position);
-        for unit in units {
-            write!(&mut result, "\n{:?}", unit.borrow())?;
+        Ok(())
+    }
+}
+
+pub fn load_replay(text: &str) -> Result<Vec<Frame>, String> {
+    let reader = FrameReader::new();
+    text.lines().filter(|line| !line.trim().is_empty()).map(|line| reader.read(line)).collect()
+}
+
+/// Shows replay frames one at a time, reading commands from `input`.
+pub fn play(frames: &[Frame], input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    const HELP: &str = "Commands: next [n] (or enter), prev [n], goto <frame>, first, last, quit";
+    if frames.is_empty() {
+        return writeln!(output, "Empty replay");
+    }
+    let mut at = 0;
+    writeln!(output, "Frame 1/{}\n{}", frames.len(), frames[0])?;
+    write!(output, "(replay) ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("next");
+        let count = words.next().and_then(|n| n.parse::<usize>().ok());
+        match command {
+            "next" | "n" => at = (at + count.unwrap_or(1)).min(frames.len() - 1),
+            "prev" | "p" => at = at.saturating_sub(count.unwrap_or(1)),
+            "goto" | "g" => match count {
+                Some(n) if (1..=frames.len()).contains(&n) => at = n - 1,
+                _ => writeln!(output, "No such frame")?,
+            },
+            "first" => at = 0,
+            "last" => at = frames.len() - 1,
+            "quit" | "q" => break,
+            _ => writeln!(output, "{}", HELP)?,
         }
-        write!(f, "{}", result)
+        writeln!(output, "Frame {}/{}\n{}", at + 1, frames.len(), frames[at])?;
+        write!(output, "(replay) ")?;
+        output.flush()?;
+    }
+    writeln!(output)
+}
+
+/// Fights the battle in `text`, with the given elf attack power if any.
+pub fn battle(text: String, elf_attack: Option<i16>) -> BattleOutcome {
+    let rules = match elf_attack {
+        Some(ap) => CombatRules::default().with_attack('E', ap),
+        None => CombatRules::default(),
+    };
+    Board::new(&parse(text), rules).run()
+}
+
+/// Fights the part 1 battle in `text`, writing a frame per round to
+/// `out`, and returns the outcome score.
+pub fn record(text: String, out: &mut dyn Write) -> io::Result<u32> {
+    Board::new(&parse(text), CombatRules::default()).play(Some(out)).map(|outcome| outcome.score())
+}
+
+/// Moves between open cells, in reading order.
+impl search::Graph for Board {
+    type State = Pos;
+
+    fn neighbors(&self, state: &Pos) -> Vec<(Pos, usize)> {
+        self.adjacent(*state).filter(|&p| self.open(p)).map(|p| (p, 1)).collect()
     }
 }
 
 impl Board {
-    fn new(input: &HashMap<Point, char>, unit_factory: &Fn(Point, char) -> Option<UnitCell>) -> Board {
-        let mut map: HashMap<Point, char> = HashMap::new();
-        let mut units: Vec<UnitCell> = Vec::new();
-        for (point, chr) in input {
-            if let Some(unit) = unit_factory(*point, *chr) {
-                units.push(unit);
-                map.insert(*point, '.');
-            } else {
-                map.insert(*point, *chr);
+    fn new(input: &Grid<char>, rules: CombatRules) -> Board {
+        let mut cave = input.clone();
+        let mut occupied = Grid::new(input.width(), input.height(), None);
+        let mut units = Vec::new();
+        for (position, chr) in input.iter() {
+            if let Some(stats) = rules.races.get(chr) {
+                occupied[position] = Some(units.len());
+                units.push(Unit::new(position, *chr, stats.hp, stats.ap));
+                cave[position] = '.';
             }
         }
-        Board { units, map, rounds: 0 }
+        let offsets = match rules.diagonal {
+            true => Pos::origin().neighbors_diagonal().collect(),
+            false => Pos::origin().neighbors().collect(),
+        };
+        Board { cave, occupied, units, rounds: 0, rules, offsets, turns: 0, aborted: false }
     }
 
-    fn get(&self, position: &Point) -> BoardSpace {
-        for unit in self.units.iter() {
-            if unit.borrow().position == *position {
-                return BoardSpace::Unit(unit.clone());
-            }
-        }
-        if let Some('.') = self.map.get(position) {
-            BoardSpace::Space
-        } else {
-            BoardSpace::Wall
-        }
+    /// Adjacent cells in reading order.
+    fn adjacent(&self, position: Pos) -> impl Iterator<Item=Pos> + '_ {
+        self.offsets.iter().map(move |&offset| position + offset)
     }
 
-    fn find_path(&self, unit: &UnitCell) -> VecDeque<Point> {
-        type State = (Point, VecDeque<Point>);
-        let mut seen: HashSet<Point> = HashSet::new();
+    fn open(&self, position: Pos) -> bool {
+        self.cave.get(position) == Some(&'.') && self.occupied[position].is_none()
+    }
 
-        let mut queue: Vec<State> = vec![];
-        for initial in unit.borrow().position.surrounding() {
-            queue.push((initial, VecDeque::new()));
-        }
-        while !queue.is_empty() {
-            let mut new_queue: Vec<State> = Vec::new();
-            for (current, path) in queue {
-                if seen.contains(&current) {
-                    continue;
-                }
-                seen.insert(current);
-
-                match self.get(&current) {
-                    BoardSpace::Wall => continue,
-                    BoardSpace::Space => (),
-                    BoardSpace::Unit(u) => {
-                        if u.borrow().race == unit.borrow().race {
-                            continue
-                        } else {
-                            return path
-                        }
-                    }
-                }
-                for diff in current.surrounding() {
-                    let mut new_path = path.clone();
-                    new_path.push_back(current);
-                    new_queue.push((diff, new_path));
+    fn enemies(&self, a: &Unit, b: &Unit) -> bool {
+        !b.dead() && !self.rules.allied(a.race, b.race)
+    }
+
+    /// Where the unit moves this turn: toward the nearest open cell in range
+    /// of an enemy, the first in reading order on ties, taking the first
+    /// step in reading order among those on a shortest path. `bfs` only
+    /// breaks ties between goals, so the step is found by a second search
+    /// back from the chosen cell to the cells next to the unit.
+    fn next_step(&self, index: usize) -> Option<Pos> {
+        let unit = &self.units[index];
+        let mut in_range = Grid::new(self.cave.width(), self.cave.height(), false);
+        for enemy in self.units.iter().filter(|other| self.enemies(unit, other)) {
+            for p in self.adjacent(enemy.position) {
+                if p == unit.position {
+                    return None;
                 }
-            }
-            queue = new_queue;
-        }
-        VecDeque::new()
-    }
-
-    fn find_enemies(&self, unit: &UnitCell) -> Vec<UnitCell> {
-        let all_dirs = [
-            Point::new(0, -1),
-            Point::new(-1, 0),
-            Point::new(1, 0),
-            Point::new(0, 1),
-        ];
-        let mut result = Vec::new();
-        for &dir in all_dirs.iter() {
-            if let BoardSpace::Unit(other) = self.get(&(unit.borrow().position + dir)) {
-                if !other.borrow().dead() && other.borrow().race != unit.borrow().race {
-                    result.push(other);
+                if self.open(p) {
+                    in_range[p] = true;
                 }
             }
         }
-        result.sort_by(|u, v| match u.borrow().hp.cmp(&v.borrow().hp) {
-            Ordering::Equal => v.borrow().position.cmp(&u.borrow().position),
-            result => result,
-        });
-        result
+        let parents = || Grid::new(self.cave.width(), self.cave.height(), None);
+        let target = search::bfs_with(self, unit.position, |&p| in_range[p], parents())?;
+        let first_steps: Vec<Pos> = self.adjacent(unit.position).collect();
+        let goal = *target.states.last().unwrap();
+        let back = search::bfs_with(self, goal, |p| first_steps.contains(p), parents())?;
+        debug_assert_eq!(back.cost + 1, target.cost);
+        back.states.last().copied()
     }
-}
 
-fn part1(input: &Input) -> u32 {
-    fn unit_creator(location: Point, race: char) -> Option<UnitCell> {
-        match race {
-            'E' => Some(Rc::new(RefCell::new(Unit::new(location, race, 200, 3)))),
-            'G' => Some(Rc::new(RefCell::new(Unit::new(location, race, 200, 3)))),
-            _ => None 
-        }
+    /// The adjacent enemy with the fewest hit points, the first in reading
+    /// order on ties.
+    fn find_enemy(&self, index: usize) -> Option<usize> {
+        let unit = &self.units[index];
+        self.adjacent(unit.position)
+            .filter_map(|p| self.occupied.get(p).copied().flatten())
+            .filter(|&other| self.enemies(unit, &self.units[other]))
+            .min_by_key(|&other| (self.units[other].hp, self.units[other].position))
     }
-    let mut board = Board::new(&input, &unit_creator);
-    'game: loop {
-        let mut order = board.units.clone();
-        order.sort_by_key(|u| u.borrow().position);
-        for unit in order.iter_mut() {
-            if unit.borrow().dead() {
+
+    /// Plays one round, returning false if it ended early because a unit
+    /// found no enemies left or the battle was aborted.
+    fn round(&mut self) -> bool {
+        let mut order: Vec<usize> = (0..self.units.len()).filter(|&i| !self.units[i].dead()).collect();
+        order.sort_by_key(|&i| self.units[i].position);
+        for index in order {
+            if self.units[index].dead() {
                 continue
             }
-            if board.units.iter().all(|u|
-               u.borrow().dead() || u.borrow().race == unit.borrow().race
-            ) {
-                board.units.retain(|u| !u.borrow().dead());
-                return board.units.iter().fold(0,
-                   |acc, u| acc + u.borrow().hp as u32) * board.rounds;
+            if !self.units.iter().any(|other| self.enemies(&self.units[index], other)) {
+                return false;
             }
-            let mut path = board.find_path(unit);
-            if let Some(pos) = path.pop_front() {
-                unit.borrow_mut().position = pos;
+            self.turns += 1;
+            if let Some(position) = self.next_step(index) {
+                self.occupied[self.units[index].position] = None;
+                self.occupied[position] = Some(index);
+                self.units[index].position = position;
             }
-            let mut enemies = board.find_enemies(unit);
-            enemies.sort_by_key(|e| -e.borrow().hp);
-            if let Some(enemy) = enemies.pop() {
-                enemy.borrow_mut().hp -= unit.borrow().ap;
+            if let Some(enemy) = self.find_enemy(index) {
+                let ap = self.units[index].ap;
+                let target = &mut self.units[enemy];
+                let damage = ap.min(target.hp);
+                target.hp -= ap;
+                let (race, killed) = (target.race, target.dead());
+                self.units[index].damage += damage as u32;
+                self.units[index].kills += killed as u32;
+                let target = &self.units[enemy];
+                if killed {
+                    self.occupied[target.position] = None;
+                    if self.rules.abort_on_death.contains(&race) {
+                        self.aborted = true;
+                        return false;
+                    }
+                }
             }
-            board.units.retain(|u| !u.borrow().dead());
         }
-        board.rounds += 1;
+        self.rounds += 1;
+        true
+    }
+
+    fn survivors(&self) -> impl Iterator<Item=&Unit> {
+        self.units.iter().filter(|u| !u.dead())
     }
-}
 
-fn part2(input: &Input) -> u32 {
-    let mut elf_attack = 3;
-    loop {
-        let unit_creator = |location: Point, race: char| -> Option<UnitCell> {
-            match race {
-                'E' => Some(Rc::new(RefCell::new(Unit::new(location, race, 200, elf_attack)))),
-                'G' => Some(Rc::new(RefCell::new(Unit::new(location, race, 200, 3)))),
-                _ => None 
+    /// Fights until only allies are left or a death aborts the battle.
+    fn run(&mut self) -> BattleOutcome {
+        self.play(None).unwrap()
+    }
+
+    /// `run`, writing a frame to `replay` before the first round and after
+    /// every round, including the one cut short.
+    fn play(&mut self, mut replay: Option<&mut dyn Write>) -> io::Result<BattleOutcome> {
+        loop {
+            if let Some(out) = &mut replay {
+                writeln!(out, "{}", self.frame().to_json())?;
             }
-        };
-        let mut board = Board::new(&input, &unit_creator);
-        'game: loop {
-            let mut order = board.units.clone();
-            order.sort_by_key(|u| u.borrow().position);
-            for unit in order.iter_mut() {
-                if unit.borrow().dead() {
-                    continue
-                }
-                if board.units.iter().all(|u|
-                   u.borrow().dead() || u.borrow().race == unit.borrow().race
-                ) {
-                    board.units.retain(|u| !u.borrow().dead());
-                    return board.units.iter().fold(0,
-                       |acc, u| acc + u.borrow().hp as u32) * board.rounds;
-                }
-                let mut path = board.find_path(unit);
-                if let Some(pos) = path.pop_front() {
-                    unit.borrow_mut().position = pos;
-                }
-                let mut enemies = board.find_enemies(unit);
-                enemies.sort_by_key(|e| -e.borrow().hp);
-                if let Some(enemy) = enemies.pop() {
-                    enemy.borrow_mut().hp -= unit.borrow().ap;
-                    if enemy.borrow().hp < 0 && enemy.borrow().race == 'E' {
-                        elf_attack += 1;
-                        break 'game;
-                    }
-                }
-                board.units.retain(|u| !u.borrow().dead());
+            if !self.round() {
+                break;
             }
-            board.rounds += 1;
         }
+        if let Some(out) = &mut replay {
+            writeln!(out, "{}", self.frame().to_json())?;
+        }
+        Ok(self.outcome())
     }
+
+    fn outcome(&self) -> BattleOutcome {
+        let mut deaths = BTreeMap::new();
+        for unit in self.units.iter().filter(|u| u.dead()) {
+            *deaths.entry(unit.race).or_insert(0) += 1;
+        }
+        let mut winners: Vec<char> = self.survivors().map(|u| u.race).filter(|_| !self.aborted).collect();
+        winners.sort();
+        winners.dedup();
+        BattleOutcome {
+            winners,
+            rounds: self.rounds,
+            turns: self.turns,
+            hp: self.survivors().fold(0, |acc, u| acc + u.hp as u32),
+            deaths,
+            aborted: self.aborted,
+            units: self.units.clone(),
+        }
+    }
+
+    fn frame(&self) -> Frame {
+        let mut map = self.cave.clone();
+        for unit in self.survivors() {
+            map[unit.position] = unit.race;
+        }
+        let rows = map.rows().map(|row| row.iter().collect()).collect();
+        let mut units: Vec<(char, Pos, i16)> = self.survivors()
+            .map(|u| (u.race, u.position, u.hp))
+            .collect();
+        units.sort_by_key(|&(_, position, _)| position);
+        Frame { round: self.rounds, rows, units }
+    }
+}
+
+fn part1(input: &Input) -> u32 {
+    Board::new(input, CombatRules::default()).run().score()
+}
+
+/// An attack power that kills any enemy of the elves on the map in one
+/// hit; attacking harder changes nothing.
+fn attack_limit(input: &Input, rules: &CombatRules) -> i16 {
+    input.iter()
+        .filter(|(_, chr)| !rules.allied('E', **chr))
+        .filter_map(|(_, chr)| rules.races.get(chr))
+        .map(|stats| stats.hp)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The lowest elf attack power that wins without losing an elf, and how
+/// that battle ends, or the limit searched up to. Any elf death aborts a
+/// battle, and winning is assumed to stay possible as attack power grows.
+fn weakest_flawless_attack(input: &Input, rules: &CombatRules) -> Result<(usize, BattleOutcome), usize> {
+    let base = rules.races.get(&'E').map_or(3, |stats| stats.ap);
+    let flawless = |ap: usize| {
+        let mut rules = rules.clone().with_attack('E', ap as i16);
+        rules.abort_on_death.push('E');
+        Some(Board::new(input, rules).run()).filter(|outcome| !outcome.aborted)
+    };
+    search::lowest(base as usize..=attack_limit(input, rules).max(base) as usize, flawless)
+}
+
+fn part2(input: &Input) -> search::Lowest<u32> {
+    let flawless = weakest_flawless_attack(input, &CombatRules::default());
+    search::Lowest(flawless.map(|(_, outcome)| outcome.score()))
 }
 
 fn parse(text: String) -> Input {
-    let mut map: HashMap<Point, char>  = HashMap::new();
-    for (r, row) in text.trim().split("\n").enumerate() {
-        for (c, chr) in row.chars().enumerate() {
-            map.insert(Point::new(c as i16, r as i16), chr);
-        }
+    text.trim().parse().unwrap()
+}
+
+pub struct Day15;
+
+impl Solution for Day15 {
+    type Input = Input;
+    type Part1 = u32;
+    type Part2 = search::Lowest<u32>;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> u32 {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> search::Lowest<u32> {
+        part2(input)
     }
-    map
 }
 
-static SAMPLE_BOARDS: [(&str, u32, u32); 6] = [("#######
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Winning race, completed rounds and hit points left.
+    type Expected = (char, u32, u32);
+
+    static SAMPLE_BOARDS: [(&str, Expected, u32, u32); 6] = [("#######
 #.G...#
 #...EG#
 #.#.#G#
 #..G#E#
 #.....#
-#######", 27730, 4988),
+#######", ('G', 47, 590), 27730, 4988),
 ("#######
 #G..#E#
 #E#E.E#
 #G.##.#
 #...#E#
 #...E.#
-#######", 36334, 0),
+#######", ('E', 37, 982), 36334, 0),
 ("#######
 #E..EG#
 #.#G.E#
 #E.##E#
 #G..#.#
 #..E#.#
-#######", 39514, 31284),
+#######", ('E', 46, 859), 39514, 31284),
 ("#######
 #E.G#.#
 #.#G..#
 #G.#.G#
 #G..#.#
 #...E.#
-#######", 27755, 3478),
+#######", ('G', 35, 793), 27755, 3478),
 ("#######
 #.E...#
 #.#..G#
 #.###.#
 #E#G#G#
 #...#G#
-#######", 28944, 6474),
+#######", ('G', 54, 536), 28944, 6474),
 ("#########
 #G......#
 #.E.#...#
@@ -353,15 +605,78 @@ static SAMPLE_BOARDS: [(&str, u32, u32); 6] = [("#######
 #...#...#
 #.G...G.#
 #.....G.#
-#########", 18740, 1140)];
+#########", ('G', 20, 937), 18740, 1140)];
+
+    #[test]
+    fn sample_boards() {
+        for (board, (winner, rounds, hp), p1_result, p2_result) in SAMPLE_BOARDS.iter() {
+            let input = parse(board.to_string());
+            let outcome = Board::new(&input, CombatRules::default()).run();
+            assert_eq!((outcome.winners.as_slice(), outcome.rounds, outcome.hp), (&[*winner][..], *rounds, *hp));
+            assert_eq!(outcome.units.iter().filter(|u| !u.dead()).map(|u| u.hp as u32).sum::<u32>(), *hp);
+            assert_eq!(part1(&input), *p1_result);
+            assert!(*p2_result == 0 || part2(&input).to_string() == p2_result.to_string());
+        }
+    }
+
+    #[test]
+    fn reports_weakest_flawless_attack() {
+        let input = parse(SAMPLE_BOARDS[0].0.to_string());
+        assert_eq!(attack_limit(&input, &CombatRules::default()), 200);
+        let (ap, outcome) = weakest_flawless_attack(&input, &CombatRules::default()).unwrap();
+        assert_eq!(ap, 15);
+        assert_eq!((outcome.rounds, outcome.hp, outcome.aborted), (29, 172, false));
+        assert_eq!(outcome.deaths, BTreeMap::from([('G', 4)]));
+        let kills: u32 = outcome.units.iter().filter(|u| u.race == 'E').map(|u| u.kills).sum();
+        let damage: u32 = outcome.units.iter().filter(|u| u.race == 'E').map(|u| u.damage).sum();
+        assert_eq!((kills, damage), (4, 4 * 200));
+
+        let table = outcome.to_string();
+        assert!(table.starts_with("Elves won after 29 rounds and "));
+        assert!(table.contains("\nG     2,1       "));
+        assert!(outcome.to_json().starts_with("{\"winners\":[\"E\"],\"rounds\":29,"));
+    }
+
+    #[test]
+    fn replays_round_trip() {
+        let mut log = Vec::new();
+        assert_eq!(record(SAMPLE_BOARDS[0].0.to_string(), &mut log).unwrap(), 27730);
+        let frames = load_replay(&String::from_utf8(log).unwrap()).unwrap();
+        assert_eq!(frames.len(), 49);
+        let last = &frames[48];
+        assert_eq!((last.round, last.units.len()), (47, 4));
+        assert_eq!(last.rows[1], "#G....#");
+        let reader = FrameReader::new();
+        assert_eq!(reader.read(&last.to_json()), Ok(last.clone()));
+        let odd = Frame { round: 1, rows: vec!["#\"\\#".to_string()], units: vec![('"', Pos::new([1, 0]), 5)] };
+        assert_eq!(reader.read(&odd.to_json()), Ok(odd.clone()));
+        let broken = odd.to_json().replace(r#""race":"\"""#, r#""race":"ab""#);
+        assert!(reader.read(&broken).is_err());
+        assert!(reader.read(&last.to_json().replace(",\"hp\":200}", "}")).is_err());
+
+        let mut screen = Vec::new();
+        play(&frames, "last\nprev 2\nq\n".as_bytes(), &mut screen).unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.contains("Frame 47/49\nRound 46\n#######   \n#G....#   G(200)\n"));
+    }
+
+    #[test]
+    fn steps_in_reading_order() {
+        let board = Board::new(&parse("#######\n#.E...#\n#.....#\n#...G.#\n#######".to_string()),
+                               CombatRules::default());
+        assert_eq!(board.next_step(0), Some(Pos::new([3, 1])));
+        let board = Board::new(&parse("#######\n#E..G.#\n#...#.#\n#.G.#G#\n#######".to_string()),
+                               CombatRules::default());
+        assert_eq!(board.next_step(0), Some(Pos::new([2, 1])));
+    }
 
-fn main() {
-    for (board, p1_result, p2_result) in SAMPLE_BOARDS.iter() {
-        let input = parse(board.to_string());
-        assert!(part1(&input)==*p1_result);
-        assert!(*p2_result==0 || part2(&input)==*p2_result);
+    #[test]
+    fn allied_races_win_together() {
+        let mut rules = CombatRules::default();
+        rules.races.insert('D', Stats { hp: 100, ap: 10 });
+        rules.alliances.push(vec!['E', 'D']);
+        let mut board = Board::new(&parse("#######\n#E.G.D#\n#######".to_string()), rules);
+        assert_eq!(board.run().score(), (152 + 100) * 16);
+        assert!(board.survivors().all(|u| u.race != 'G'));
     }
-    let input = parse(get_input(15, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
 }
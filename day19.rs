@@ -1,72 +1,44 @@
-use common::get_input;
-use common::op_code;
+use common::op_code::{load, Program, Vm};
+use common::Solution;
 
-struct Input {
-    ip: usize,
-    program: Vec<op_code::Instruction>
-}
+type Input = Program;
 
-fn factors(num: usize) -> Vec<usize> {
-    let mut result = vec![]; 
-    for n in 1..((num as f64).sqrt() as usize + 1) {
-        if num % n == 0 {
-            result.push(n);
-            result.push(num / n);
-        }
-    }
-    result
+fn run(code: &Input, first: usize) -> usize {
+    let mut vm = Vm::new(code, 6);
+    vm.optimize();
+    vm.registers[0] = first;
+    vm.run();
+    vm.registers[0]
 }
 
 fn part1(code: &Input) -> usize {
-    let mut registers = [0; 6];
-    let opcodes = op_code::new();
-    let ip = code.ip;
-    while registers[ip] != 1 {
-        if let Some(instr) = code.program.get(registers[ip]) {
-            registers = opcodes[&instr.name](instr.reg, registers);
-            registers[ip] += 1;
-        } else {
-            return registers[0]
-        }
-    }
-    factors(registers[1]).iter().sum()
+    run(code, 0)
 }
 
 fn part2(code: &Input) -> usize {
-    let mut registers = [0; 6];
-    registers[0] = 1;
-    let opcodes = op_code::new();
-    let ip = code.ip;
-    while registers[ip] != 1 {
-        if let Some(instr) = code.program.get(registers[ip]) {
-            registers = opcodes[&instr.name](instr.reg, registers);
-            registers[ip] += 1;
-        } else {
-            return registers[0]
-        }
-    }
-    factors(registers[1]).iter().sum()
+    run(code, 1)
 }
 
 fn parse(text: String) -> Input {
-    let mut first: Option<usize> = None;
-    let mut program: Vec<op_code::Instruction> = vec![];
-    for (n, line) in text.trim().split('\n').enumerate() {
-        if n == 0 {
-            first = Some(line.split(' ').skip(1).next().unwrap()
-                 .parse::<usize>().unwrap());
-        } else {
-            program.push(op_code::Instruction::new(line));
-        }
+    load(&text, 6).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub struct Day19;
+
+impl Solution for Day19 {
+    type Input = Input;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(text: String) -> Input {
+        parse(text)
     }
-    Input {
-        ip: first.unwrap(),
-        program: program,
+
+    fn part1(input: &Input) -> usize {
+        part1(input)
     }
-}
 
-fn main() {
-    let input = parse(get_input(19, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    fn part2(input: &Input) -> usize {
+        part2(input)
+    }
 }
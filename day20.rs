@@ -1,28 +1,28 @@
 use std::collections::{HashSet, HashMap, VecDeque};
 use std::iter::FromIterator;
 
-use common::get_input;
 use common::point::Point;
+use common::search::{self, Graph};
+use common::Solution;
 
 type Input = HashMap<Point, HashSet<Point>>;
 
-fn map_distances(map: &Input) -> HashMap<Point, usize> {
-    let mut queue = VecDeque::from_iter(vec![(Point::new(0, 0), 0)]);
-    let mut door_counts: HashMap<Point, usize> = HashMap::new();
-    while let Some((position, doors)) = queue.pop_front() {
-        if door_counts.contains_key(&position) {
-            continue
-        }
-        door_counts.insert(position, doors);
-        for next_position in map.get(&position).unwrap().iter() {
-            queue.push_front((next_position.clone(), doors + 1));
-        }
+struct Doors<'a>(&'a Input);
+
+impl Graph for Doors<'_> {
+    type State = Point;
+
+    fn neighbors(&self, room: &Point) -> Vec<(Point, usize)> {
+        self.0[room].iter().map(|&next| (next, 1)).collect()
     }
-    door_counts
+}
+
+fn map_distances(map: &Input) -> HashMap<Point, usize> {
+    search::distances(&Doors(map), Point::new(0, 0))
 }
 
 fn part1(input: &Input) -> usize {
-    map_distances(input).values().max().unwrap().clone()
+    *map_distances(input).values().max().unwrap()
 }
 
 fn part2(input: &Input) -> usize {
@@ -40,7 +40,7 @@ fn parse(text: String) -> Input {
         match dir {
             '(' => { stack.push_front(location); },
             ')' => { location = stack.pop_front().unwrap(); },
-            '|' => { location = stack.front().unwrap().clone(); },
+            '|' => { location = *stack.front().unwrap(); },
             _ => {
                 let next_location = location + match dir {
                     'N' => Point::new(0, 1),
@@ -50,22 +50,48 @@ fn parse(text: String) -> Input {
                     c => panic!("What is this? {}", c),
                 };
                 door_map.entry(next_location)
-                    .or_insert(HashSet::new())
+                    .or_default()
                     .insert(location);
                 door_map.entry(location)
-                    .or_insert(HashSet::new())
+                    .or_default()
                     .insert(next_location);
                 location = next_location;
             },
         }
     }
-    assert!(stack.len() == 0);
+    assert!(stack.is_empty());
     door_map
 }
 
-fn main() {
-    assert!(part1(&parse("^WNE$".to_string())) == 3);
-    let input = parse(get_input(20, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day20;
+
+impl Solution for Day20 {
+    type Input = Input;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> usize {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> usize {
+        part2(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_route() {
+        assert_eq!(part1(&parse("^WNE$".to_string())), 3);
+        assert_eq!(part1(&parse("^ENWWW(NEEE|SSE(EE|N))$".to_string())), 10);
+        assert_eq!(part1(&parse("^ESSWWN(E|NNENN(EESS(WNSE|)SSS|WWWSSSSE(SW|NNNE)))$".to_string())), 23);
+        assert_eq!(part1(&parse("^NESW$".to_string())), 2);
+    }
 }
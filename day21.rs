@@ -1,80 +1,76 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
-use common::get_input;
-use common::op_code::{self, Instruction};
+use common::cycle;
+use common::op_code::{load, Opcode, Program, Vm};
+use common::Solution;
 
-struct Input {
-    ip: usize,
-    program: Vec<Instruction>,
+type Input = Program;
+
+/// The instruction that halts the program by comparing a register with r0,
+/// and that register.
+fn halting_check(program: &Program) -> (usize, usize) {
+    program.instructions.iter().enumerate()
+        .find_map(|(at, instr)| match (instr.op, instr.args) {
+            (Opcode::Eqrr, [0, reg, _]) | (Opcode::Eqrr, [reg, 0, _]) if reg != 0 => Some((at, reg)),
+            _ => None,
+        })
+        .expect("No comparison with register 0")
 }
 
 fn part1(input: &Input) -> usize {
-    let mut registers = [0; 6];
-    let opcodes = op_code::new();
-    while registers[input.ip] != 28 {
-        if let Some(instr) = input.program.get(registers[input.ip]) {
-            registers = opcodes[&instr.name](instr.reg, registers);
-            registers[input.ip] += 1;
-        } else {
-            return registers[0]
-        }
+    let (check, value) = halting_check(input);
+    let mut vm = Vm::new(input, 6);
+    vm.optimize();
+    if vm.run_until(|vm| vm.ip == check) {
+        vm.registers[value]
+    } else {
+        vm.registers[0]
     }
-    registers[3]
 }
 
-fn part2(_: &Input) -> usize {
-    let mut seen = HashSet::new();
-    let mut state = (0, 0);
-    let mut steps = 0;
+fn part2(input: &Input) -> usize {
+    let (check, value) = halting_check(input);
+    let mut vm = Vm::new(input, 6);
+    vm.optimize();
+    vm.run_until(|vm| vm.ip == check);
+    let first = vm.registers.clone();
+    let next_check = |registers: &Vec<usize>| {
+        vm.registers = registers.clone();
+        vm.ip = check;
+        vm.step();
+        vm.run_until(|vm| vm.ip == check);
+        vm.registers.clone()
+    };
+    let history = cycle::detect(first, next_check);
     let mut min_steps: HashMap<usize, usize> = HashMap::new();
-    while !seen.contains(&state) {
-        seen.insert(state);
-        if !min_steps.contains_key(&state.0) {
-            min_steps.insert(state.0, steps);
-        }
-        steps += 1;
-        state = update_func(state.0)
+    for (steps, registers) in history.states.iter().enumerate() {
+        min_steps.entry(registers[value]).or_insert(steps);
     }
     min_steps.into_iter()
         .max_by(|&a, &b| a.1.partial_cmp(&b.1).unwrap())
         .unwrap().0
 }
 
-fn update_func(mut reg3: usize) -> (usize, usize) {
-    let mut reg5 = 65536 | reg3;
-    reg3 = 5557974;
-    loop {
-        reg3 += reg5 & 255;
-        reg3 &= 16777215;
-        reg3 *= 65899;
-        reg3 &= 16777215;
-        if reg5 < 256 {
-            break
-        }
-        reg5 /= 256;
-    }
-    (reg3, reg5)
+fn parse(text: String) -> Input {
+    load(&text, 6).unwrap_or_else(|err| panic!("{}", err))
 }
 
-fn parse(text: String) -> Input {
-    let mut first: Option<usize> = None;
-    let mut program: Vec<Instruction> = Vec::new();
-    for (n, line) in text.trim().split('\n').enumerate() {
-        if n != 0 {
-            program.push(Instruction::new(line));
-        } else {
-            first = Some(line.split(' ').skip(1).next().unwrap()
-                .parse::<usize>().unwrap());
-        }
+pub struct Day21;
+
+impl Solution for Day21 {
+    type Input = Input;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(text: String) -> Input {
+        parse(text)
     }
-    Input {
-        ip: first.unwrap(),
-        program: program,
+
+    fn part1(input: &Input) -> usize {
+        part1(input)
     }
-}
 
-fn main() {
-    let input = parse(get_input(21, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    fn part2(input: &Input) -> usize {
+        part2(input)
+    }
 }
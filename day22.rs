@@ -1,10 +1,11 @@
-use std::collections::{HashSet, HashMap, BinaryHeap};
-use std::cmp::Ordering;
+use std::cell::RefCell;
+use std::collections::HashMap;
 
-use common::get_input;
 use common::point::{self, Point};
+use common::search::{self, Graph};
+use common::Solution;
 
-struct Input {
+pub struct Input {
     depth: usize,
     target: Point,
 }
@@ -18,8 +19,8 @@ struct GeologicalMap {
 impl GeologicalMap {
     fn new(depth: usize, target: Point) -> GeologicalMap {
         GeologicalMap {
-            depth: depth,
-            target: target,
+            depth,
+            target,
             values: HashMap::new(),
         }
     }
@@ -53,86 +54,57 @@ fn part1(input: &Input) -> usize {
     total
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 enum Gear {
-    NONE = 0,
-    TORCH = 1,
-    CLIMB = 2,
-}
-
-#[derive(Debug, PartialEq, Eq)]
-struct State {
-    distance: usize,
-    time: usize,
-    position: Point,
-    gear: Gear,
-}
-
-impl Ord for State {
-    fn cmp(&self, other: &State) -> Ordering {
-        other.distance.cmp(&self.distance)
-    }
-}
-
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &State) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+    None = 0,
+    Torch = 1,
+    Climb = 2,
 }
 
 impl Gear {
     fn get_compliment(self, terrain: usize) -> Gear {
         match 3 - terrain - self as usize {
-            0 => Gear::NONE,
-            1 => Gear::TORCH,
-            2 => Gear::CLIMB,
+            0 => Gear::None,
+            1 => Gear::Torch,
+            2 => Gear::Climb,
             n => panic!("Not a gear integer {}", n),
         }
     }
 }
 
-fn part2(input: &Input) -> usize {
-    let mut mapping = GeologicalMap::new(input.depth, input.target);
-    let mut seen: HashSet<(Point, Gear)> = HashSet::new();
-    let mut queue = BinaryHeap::new();
-    queue.push(State {
-        distance: Point::new(0, 0).distance(input.target),
-        time: 0,
-        position: Point::new(0, 0),
-        gear: Gear::TORCH
-    });
-
-    while let Some(state) = queue.pop() {
-        if state.position == mapping.target && state.gear == Gear::TORCH {
-            return state.time
-        }
-        if state.position.x < 0 || state.position.y < 0 ||
-           mapping.index(state.position) % 3 == (state.gear as usize) ||
-           seen.contains(&(state.position, state.gear)) {
-            continue
-        }
-        seen.insert((state.position, state.gear));
-        let other_gear = state.gear.get_compliment(mapping.index(state.position) % 3);
-        queue.push(State {
-            distance: state.time + 7 + input.target.distance(state.position),
-            time: state.time + 7,
-            position: state.position,
-            gear: other_gear,
-        });
-
-        for directions in point::directions().iter() {
-            let new_position = state.position + *directions;
-            queue.push(
-                State {
-                    distance: state.time + 1 + input.target.distance(new_position),
-                    time: state.time + 1,
-                    position: new_position,
-                    gear: state.gear,
-                });
+struct Cave(RefCell<GeologicalMap>);
+
+impl Cave {
+    fn terrain(&self, point: Point) -> usize {
+        self.0.borrow_mut().index(point) % 3
+    }
+}
+
+impl Graph for Cave {
+    type State = (Point, Gear);
+
+    fn neighbors(&self, &(position, gear): &(Point, Gear)) -> Vec<((Point, Gear), usize)> {
+        let mut moves = vec![((position, gear.get_compliment(self.terrain(position))), 7)];
+        for direction in point::directions().iter() {
+            let next = position + *direction;
+            if next.x >= 0 && next.y >= 0 && self.terrain(next) != gear as usize {
+                moves.push(((next, gear), 1));
+            }
         }
+        moves
+    }
 
+    fn heuristic(&self, &(position, _): &(Point, Gear)) -> usize {
+        position.distance(self.0.borrow().target)
     }
-    panic!("Cannot be done")
+}
+
+fn part2(input: &Input) -> usize {
+    let cave = Cave(RefCell::new(GeologicalMap::new(input.depth, input.target)));
+    let target = (input.target, Gear::Torch);
+    search::astar(&cave, (Point::new(0, 0), Gear::Torch), |state| *state == target)
+        .expect("Cannot be done")
+        .cost
 }
 
 fn parse(input: String) -> Input {
@@ -141,12 +113,26 @@ fn parse(input: String) -> Input {
         .collect();
     Input {
         depth: values[0].parse::<usize>().unwrap(),
-        target: Point::from_str(values[1]),
+        target: values[1].parse::<Point>().unwrap(),
     }
 }
 
-fn main() {
-    let input = parse(get_input(22, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day22;
+
+impl Solution for Day22 {
+    type Input = Input;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> usize {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> usize {
+        part2(input)
+    }
 }
@@ -1,14 +1,12 @@
-extern crate regex;
-
 use regex::Regex;
 use std::collections::HashSet;
 
-use common::get_input;
 use common::point::Point3d;
+use common::Solution;
 
 type Input = Vec<Bot>;
 
-struct Bot {
+pub struct Bot {
     position: Point3d<i32>,
     radius: usize,
 }
@@ -16,7 +14,7 @@ struct Bot {
 impl Bot {
     fn from_line(line: &str) -> Bot {
         let re = Regex::new(r"pos=<(-?\d+),(-?\d+),(-?\d+)>, r=(\d+)").unwrap();
-        let cap = re.captures(&line).unwrap();
+        let cap = re.captures(line).unwrap();
         Bot {
             position: Point3d::new(
               cap.get(1).unwrap().as_str().parse::<i32>().unwrap(),
@@ -36,7 +34,7 @@ fn part1(input: &Input) -> usize {
     ).count()
 }
 
-fn bot_in_box_range(bots: &Vec<Bot>, position: &Point3d<i32>, radius: usize) -> usize {
+fn bot_in_box_range(bots: &[Bot], position: &Point3d<i32>, radius: usize) -> usize {
     bots.iter()
         .filter(|b| position.distance(&b.position) <= radius + b.radius)
         .count()
@@ -70,7 +68,7 @@ fn steps(radius: usize) -> Vec<Point3d<i32>> {
 fn part2(input: &Input) -> usize {
     let position = Point3d::new(0, 0, 0);
     let mut max_radius = 1;
-    while bot_in_box_range(&&input, &position, max_radius) < input.len() {
+    while bot_in_box_range(input, &position, max_radius) < input.len() {
         max_radius *= 2;
     }
     let mut positions = HashSet::new();
@@ -111,8 +109,22 @@ fn parse(input: String) -> Input {
     bots
 }
 
-fn main() {
-    let input = parse(get_input(23, 2018));
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+pub struct Day23;
+
+impl Solution for Day23 {
+    type Input = Input;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> usize {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> usize {
+        part2(input)
+    }
 }
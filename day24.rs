@@ -0,0 +1,219 @@
+use std::cmp::Reverse;
+
+use regex::Regex;
+
+use common::search;
+use common::Solution;
+
+type Input = Vec<Group>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Army {
+    Immune,
+    Infection,
+}
+
+#[derive(Clone, Debug)]
+pub struct Group {
+    army: Army,
+    units: u32,
+    hp: u32,
+    ap: u64,
+    damage_type: String,
+    initiative: u32,
+    weak: Vec<String>,
+    immune: Vec<String>,
+}
+
+impl Group {
+    fn effective_power(&self) -> u64 {
+        (self.units as u64).saturating_mul(self.ap)
+    }
+
+    fn damage(&self, other: &Group) -> u64 {
+        if other.immune.contains(&self.damage_type) {
+            0
+        } else if other.weak.contains(&self.damage_type) {
+            self.effective_power().saturating_mul(2)
+        } else {
+            self.effective_power()
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Won(Army, u32),
+    Stalemate,
+}
+
+fn select_targets(groups: &[Group]) -> Vec<Option<usize>> {
+    let mut order: Vec<usize> = (0..groups.len()).collect();
+    order.sort_by_key(|&g| Reverse((groups[g].effective_power(), groups[g].initiative)));
+    let mut targets = vec![None; groups.len()];
+    let mut taken = vec![false; groups.len()];
+    for attacker in order {
+        let target = (0..groups.len())
+            .filter(|&d| !taken[d] && groups[d].army != groups[attacker].army)
+            .filter(|&d| groups[attacker].damage(&groups[d]) > 0)
+            .max_by_key(|&d| (
+                groups[attacker].damage(&groups[d]),
+                groups[d].effective_power(),
+                groups[d].initiative,
+            ));
+        if let Some(d) = target {
+            taken[d] = true;
+            targets[attacker] = Some(d);
+        }
+    }
+    targets
+}
+
+/// Runs one round and returns the number of units killed.
+fn round(groups: &mut Vec<Group>) -> u32 {
+    let targets = select_targets(groups);
+    let mut order: Vec<usize> = (0..groups.len()).collect();
+    order.sort_by_key(|&g| Reverse(groups[g].initiative));
+    let mut killed = 0;
+    for attacker in order {
+        if let Some(defender) = targets[attacker] {
+            if groups[attacker].units == 0 {
+                continue;
+            }
+            let damage = groups[attacker].damage(&groups[defender]);
+            let dead = (damage / groups[defender].hp as u64).min(groups[defender].units as u64) as u32;
+            groups[defender].units -= dead;
+            killed += dead;
+        }
+    }
+    groups.retain(|g| g.units > 0);
+    killed
+}
+
+fn fight(start: &[Group], boost: u32) -> Outcome {
+    let mut groups = start.to_vec();
+    for group in groups.iter_mut().filter(|g| g.army == Army::Immune) {
+        group.ap += boost as u64;
+    }
+    while groups.iter().any(|g| g.army != groups[0].army) {
+        if round(&mut groups) == 0 {
+            return Outcome::Stalemate;
+        }
+    }
+    Outcome::Won(groups[0].army, groups.iter().map(|g| g.units).sum())
+}
+
+fn part1(input: &Input) -> u32 {
+    match fight(input, 0) {
+        Outcome::Won(_, units) => units,
+        Outcome::Stalemate => panic!("Nobody wins without a boost"),
+    }
+}
+
+/// A boost large enough for any immune group to wipe out any infection
+/// group it can damage in one attack; boosting further changes nothing.
+fn boost_limit(input: &Input) -> u32 {
+    let limit = input.iter()
+        .filter(|g| g.army == Army::Infection)
+        .map(|g| g.hp as u64 * g.units as u64)
+        .max()
+        .unwrap_or(0);
+    limit.min(u32::MAX as u64) as u32
+}
+
+fn part2(input: &Input) -> search::Lowest<u32> {
+    let immune_wins = |boost: usize| match fight(input, boost as u32) {
+        Outcome::Won(Army::Immune, units) => Some(units),
+        _ => None,
+    };
+    let rescue = search::lowest(0..=boost_limit(input) as usize, immune_wins);
+    search::Lowest(rescue.map(|(_, units)| units))
+}
+
+fn parse(text: String) -> Input {
+    let unit = Regex::new(concat!(
+        r"(\d+) units each with (\d+) hit points ",
+        r"(?:\((.*?)\) )?",
+        r"with an attack that does (\d+) (\w+) damage ",
+        r"at initiative (\d+)",
+    )).unwrap();
+    let mut groups = Vec::new();
+    let mut army = None;
+    for line in text.trim().lines() {
+        match line.trim() {
+            "" => continue,
+            "Immune System:" => army = Some(Army::Immune),
+            "Infection:" => army = Some(Army::Infection),
+            line => {
+                let cap = unit.captures(line).unwrap();
+                let mut group = Group {
+                    army: army.expect("Group before army heading"),
+                    units: cap[1].parse().unwrap(),
+                    hp: cap[2].parse().unwrap(),
+                    ap: cap[4].parse().unwrap(),
+                    damage_type: cap[5].to_string(),
+                    initiative: cap[6].parse().unwrap(),
+                    weak: Vec::new(),
+                    immune: Vec::new(),
+                };
+                if let Some(attributes) = cap.get(3) {
+                    for attribute in attributes.as_str().split("; ") {
+                        let types = |prefix: &str| attribute[prefix.len()..]
+                            .split(", ").map(|t| t.to_string()).collect();
+                        if attribute.starts_with("weak to ") {
+                            group.weak = types("weak to ");
+                        } else if attribute.starts_with("immune to ") {
+                            group.immune = types("immune to ");
+                        } else {
+                            panic!("What is this? {}", attribute);
+                        }
+                    }
+                }
+                groups.push(group);
+            }
+        }
+    }
+    groups
+}
+
+pub struct Day24;
+
+impl Solution for Day24 {
+    type Input = Input;
+    type Part1 = u32;
+    type Part2 = search::Lowest<u32>;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> u32 {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> search::Lowest<u32> {
+        part2(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SAMPLE: &str = "Immune System:
+17 units each with 5390 hit points (weak to radiation, bludgeoning) with an attack that does 4507 fire damage at initiative 2
+989 units each with 1274 hit points (immune to fire; weak to bludgeoning, slashing) with an attack that does 25 slashing damage at initiative 3
+
+Infection:
+801 units each with 4706 hit points (weak to radiation) with an attack that does 116 bludgeoning damage at initiative 1
+4485 units each with 2961 hit points (immune to radiation; weak to fire, cold) with an attack that does 12 slashing damage at initiative 4";
+
+    #[test]
+    fn sample_armies() {
+        let input = parse(SAMPLE.to_string());
+        assert_eq!(part1(&input), 5216);
+        assert_eq!(fight(&input, 1570), Outcome::Won(Army::Immune, 51));
+        assert_eq!(boost_limit(&input), 4485 * 2961);
+        assert_eq!(part2(&input).to_string(), "51");
+    }
+}
@@ -0,0 +1,102 @@
+use common::point::PointN;
+use common::Solution;
+
+type Point = PointN<i32, 4>;
+type Input = Vec<Point>;
+
+/// Union-find over point indexes, with path halving and union by size.
+struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(n: usize) -> DisjointSet {
+        DisjointSet { parent: (0..n).collect(), size: vec![1; n] }
+    }
+
+    fn find(&mut self, mut item: usize) -> usize {
+        while self.parent[item] != item {
+            self.parent[item] = self.parent[self.parent[item]];
+            item = self.parent[item];
+        }
+        item
+    }
+
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        true
+    }
+}
+
+fn constellations(points: &[Point], reach: usize) -> usize {
+    let mut sorted = points.to_vec();
+    sorted.sort_by_key(|p| p.coords[0]);
+    let mut sets = DisjointSet::new(sorted.len());
+    let mut count = sorted.len();
+    for (i, p) in sorted.iter().enumerate() {
+        for (j, q) in sorted.iter().enumerate().skip(i + 1) {
+            if (q.coords[0] - p.coords[0]) as usize > reach {
+                break;
+            }
+            if p.distance(q) <= reach && sets.union(i, j) {
+                count -= 1;
+            }
+        }
+    }
+    count
+}
+
+fn part1(input: &Input) -> usize {
+    constellations(input, 3)
+}
+
+fn part2(_: &Input) -> &'static str {
+    "No part 2 on day 25"
+}
+
+fn parse(text: String) -> Input {
+    text.trim().lines().map(|line| line.parse::<Point>().unwrap()).collect()
+}
+
+pub struct Day25;
+
+impl Solution for Day25 {
+    type Input = Input;
+    type Part1 = usize;
+    type Part2 = &'static str;
+
+    fn parse(text: String) -> Input {
+        parse(text)
+    }
+
+    fn part1(input: &Input) -> usize {
+        part1(input)
+    }
+
+    fn part2(input: &Input) -> &'static str {
+        part2(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_constellations() {
+        let first = "0,0,0,0\n3,0,0,0\n0,3,0,0\n0,0,3,0\n0,0,0,3\n0,0,0,6\n9,0,0,0\n12,0,0,0";
+        let second = "-1,2,2,0\n0,0,2,-2\n0,0,0,-2\n-1,2,0,0\n-2,-2,-2,2\n\
+                      3,0,2,-1\n-1,3,2,2\n-1,0,-1,0\n0,2,1,-2\n3,0,0,0";
+        assert_eq!(part1(&parse(first.to_string())), 2);
+        assert_eq!(part1(&parse(second.to_string())), 4);
+    }
+}
@@ -1,21 +1,300 @@
-use std::io::prelude::*;
-use std::fs::File;
-
-pub fn get_input(day: u8, year: u16) -> String {
-    let file_name = format!(".AoC-{:04}-{:02}.tmp", year, day);
-    let file = File::open(file_name);
-    let mut result = String::new();
-    if let Ok(mut f) = file {
-        f.read_to_string(&mut result).expect("Unable to read file");
-    } else {
-        unimplemented!()
-    }
-    return result;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+pub use input::{get_input, InputError};
+
+pub mod input {
+    use std::env;
+    use std::error::Error;
+    use std::fmt;
+    use std::fs;
+    use std::io::{self, Read};
+    use std::path::{Path, PathBuf};
+
+    /// Environment variable naming the directory that holds the cached inputs.
+    pub const INPUT_DIR_VAR: &str = "AOC_INPUT_DIR";
+    pub const AOC_URL: &str = "https://adventofcode.com";
+    pub const SESSION_FILE: &str = ".token";
+
+    #[derive(Debug)]
+    pub enum InputError {
+        Missing(PathBuf),
+        Unreadable(PathBuf, io::Error),
+        Empty(PathBuf),
+        Encoding(PathBuf),
+        Unwritable(PathBuf, io::Error),
+        Download(String, String),
+    }
+
+    impl fmt::Display for InputError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                InputError::Missing(path) =>
+                    write!(f, "Input file {} does not exist", path.display()),
+                InputError::Unreadable(path, err) =>
+                    write!(f, "Unable to read input file {}: {}", path.display(), err),
+                InputError::Empty(path) =>
+                    write!(f, "Input file {} is empty", path.display()),
+                InputError::Encoding(path) =>
+                    write!(f, "Input file {} is not valid UTF-8", path.display()),
+                InputError::Unwritable(path, err) =>
+                    write!(f, "Unable to write input file {}: {}", path.display(), err),
+                InputError::Download(url, reason) =>
+                    write!(f, "Could not get {}: {}", url, reason),
+            }
+        }
+    }
+
+    impl Error for InputError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            match self {
+                InputError::Unreadable(_, err) | InputError::Unwritable(_, err) => Some(err),
+                _ => None,
+            }
+        }
+    }
+
+    /// Somewhere puzzle inputs can be loaded from.
+    pub trait InputSource {
+        fn fetch(&self, day: u8, year: u16) -> Result<String, InputError>;
+    }
+
+    pub fn file_name(day: u8, year: u16) -> String {
+        format!(".AoC-{:04}-{:02}.tmp", year, day)
+    }
+
+    /// The directory named by `AOC_INPUT_DIR`, or the working directory.
+    pub fn input_dir() -> PathBuf {
+        env::var_os(INPUT_DIR_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    fn read_file(path: PathBuf) -> Result<String, InputError> {
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound =>
+                return Err(InputError::Missing(path)),
+            Err(err) => return Err(InputError::Unreadable(path, err)),
+        };
+        decode(path, bytes)
+    }
+
+    fn decode(path: PathBuf, bytes: Vec<u8>) -> Result<String, InputError> {
+        let text = match String::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(_) => return Err(InputError::Encoding(path)),
+        };
+        if text.trim().is_empty() {
+            return Err(InputError::Empty(path));
+        }
+        Ok(text)
+    }
+
+    pub fn read_input(dir: &Path, day: u8, year: u16) -> Result<String, InputError> {
+        read_file(dir.join(file_name(day, year)))
+    }
+
+    pub fn get_input(day: u8, year: u16) -> Result<String, InputError> {
+        read_input(&input_dir(), day, year)
+    }
+
+    /// One explicit file used for whichever day is asked for, or standard
+    /// input when the path is `-`.
+    pub struct PathSource {
+        pub path: PathBuf,
+    }
+
+    impl PathSource {
+        pub fn new(path: &Path) -> PathSource {
+            PathSource { path: path.to_path_buf() }
+        }
+
+        pub fn is_stdin(&self) -> bool {
+            self.path == Path::new("-")
+        }
+    }
+
+    impl InputSource for PathSource {
+        fn fetch(&self, _day: u8, _year: u16) -> Result<String, InputError> {
+            if !self.is_stdin() {
+                return read_file(self.path.clone());
+            }
+            let mut bytes = Vec::new();
+            match io::stdin().lock().read_to_end(&mut bytes) {
+                Ok(_) => decode(self.path.clone(), bytes),
+                Err(err) => Err(InputError::Unreadable(self.path.clone(), err)),
+            }
+        }
+    }
+
+    /// Inputs cached on disk as `.AoC-YYYY-DD.tmp`, the same files
+    /// `get_input.py` writes.
+    pub struct FileCache {
+        pub dir: PathBuf,
+    }
+
+    impl FileCache {
+        pub fn new(dir: &Path) -> FileCache {
+            FileCache { dir: dir.to_path_buf() }
+        }
+
+        pub fn store(&self, day: u8, year: u16, text: &str) -> Result<(), InputError> {
+            let path = self.dir.join(file_name(day, year));
+            fs::write(&path, text).map_err(|err| InputError::Unwritable(path, err))
+        }
+    }
+
+    impl InputSource for FileCache {
+        fn fetch(&self, day: u8, year: u16) -> Result<String, InputError> {
+            read_input(&self.dir, day, year)
+        }
+    }
+
+    /// Downloads inputs from `{base_url}/{year}/day/{day}/input` using a
+    /// session cookie.
+    pub struct HttpFetcher {
+        pub base_url: String,
+        session: String,
+    }
+
+    impl HttpFetcher {
+        pub fn new(base_url: &str, session: &str) -> HttpFetcher {
+            HttpFetcher {
+                base_url: base_url.trim_end_matches('/').to_string(),
+                session: session.trim().to_string(),
+            }
+        }
+
+        /// Reads the session cookie from a file such as `.token`.
+        pub fn from_token_file(base_url: &str, path: &Path) -> Result<HttpFetcher, InputError> {
+            let session = read_file(path.to_path_buf())?;
+            Ok(HttpFetcher::new(base_url, &session))
+        }
+
+        pub fn url(&self, day: u8, year: u16) -> String {
+            format!("{}/{}/day/{}/input", self.base_url, year, day)
+        }
+    }
+
+    impl InputSource for HttpFetcher {
+        fn fetch(&self, day: u8, year: u16) -> Result<String, InputError> {
+            let url = self.url(day, year);
+            let response = ureq::get(&url)
+                .set("Cookie", &format!("session={}", self.session))
+                .call();
+            match response {
+                Ok(response) => response.into_string()
+                    .map_err(|err| InputError::Download(url, err.to_string())),
+                Err(ureq::Error::Status(code, response)) => Err(InputError::Download(
+                    url, format!("{}: {}", code, response.status_text()))),
+                Err(err) => Err(InputError::Download(url, err.to_string())),
+            }
+        }
+    }
+
+    /// Reads from the cache, falling back to `fetcher` and saving what it
+    /// returns when the cached file is missing.
+    pub struct CachedSource<S> {
+        pub cache: FileCache,
+        pub fetcher: S,
+    }
+
+    impl<S: InputSource> InputSource for CachedSource<S> {
+        fn fetch(&self, day: u8, year: u16) -> Result<String, InputError> {
+            match self.cache.fetch(day, year) {
+                Err(InputError::Missing(_)) => {
+                    let text = self.fetcher.fetch(day, year)?;
+                    self.cache.store(day, year, &text)?;
+                    Ok(text)
+                },
+                result => result,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::io::Write;
+        use std::net::TcpListener;
+        use std::thread;
+
+        #[test]
+        fn downloads_and_caches_missing_input() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            let server = thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 1024];
+                let size = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..size]).to_string();
+                stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\n+1\n-2\n"
+                ).unwrap();
+                request
+            });
+
+            let dir = env::temp_dir().join(format!("aoc-input-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let source = CachedSource {
+                cache: FileCache::new(&dir),
+                fetcher: HttpFetcher::new(&base_url, "secret\n"),
+            };
+            assert_eq!(source.fetch(1, 2018).unwrap(), "+1\n-2\n");
+            let request = server.join().unwrap();
+            assert!(request.starts_with("GET /2018/day/1/input "));
+            assert!(request.contains("session=secret"));
+            assert_eq!(read_input(&dir, 1, 2018).unwrap(), "+1\n-2\n");
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
+
+/// A single day's puzzle: how to parse the input and answer both parts.
+pub trait Solution {
+    type Input;
+    type Part1: fmt::Display;
+    type Part2: fmt::Display;
+
+    fn parse(text: String) -> Self::Input;
+    fn part1(input: &Self::Input) -> Self::Part1;
+    fn part2(input: &Self::Input) -> Self::Part2;
+}
+
+pub struct Answer {
+    pub part: u8,
+    pub value: String,
+    pub elapsed: Duration,
+}
+
+/// Parses `text` and solves the requested part, or both when `part` is
+/// `None`. The parse time is folded into the first answer returned.
+pub fn solve<S: Solution>(text: String, part: Option<u8>) -> Vec<Answer> {
+    let start = Instant::now();
+    let input = S::parse(text);
+    let mut parse_time = start.elapsed();
+    let mut answers = Vec::new();
+    if part.is_none() || part == Some(1) {
+        let start = Instant::now();
+        let value = S::part1(&input).to_string();
+        answers.push(Answer { part: 1, value, elapsed: parse_time + start.elapsed() });
+        parse_time = Duration::from_secs(0);
+    }
+    if part.is_none() || part == Some(2) {
+        let start = Instant::now();
+        let value = S::part2(&input).to_string();
+        answers.push(Answer { part: 2, value, elapsed: parse_time + start.elapsed() });
+    }
+    answers
 }
 
 pub mod point {
-    use std::ops::{Add, Sub};
+    use std::cmp::Ordering;
+    use std::error::Error;
+    use std::ops::{Add, Mul, Neg, Sub};
     use std::fmt;
+    use std::str::FromStr;
 
     #[derive(Clone, Copy, Eq, PartialEq, Hash)]
     pub struct Point {
@@ -28,16 +307,17 @@ pub mod point {
             Point { x, y }
         }
 
-        pub fn from_str(text: &str) -> Point {
-            let values = text.split(',').collect::<Vec<&str>>();
-            Point::new(
-                values[0].parse::<i32>().unwrap(),
-                values[1].parse::<i32>().unwrap(),
-            )
+        pub fn distance(self, to: Point) -> usize {
+            (self.x.abs_diff(to.x) + self.y.abs_diff(to.y)) as usize
         }
+    }
 
-        pub fn distance(self, to: Point) -> usize {
-            ((self.x - to.x).abs() + (self.y - to.y).abs()) as usize
+    impl FromStr for Point {
+        type Err = ParsePointError;
+
+        fn from_str(text: &str) -> Result<Point, ParsePointError> {
+            let PointN { coords: [x, y] } = text.parse::<PointN<i32, 2>>()?;
+            Ok(Point::new(x, y))
         }
     }
 
@@ -57,6 +337,18 @@ pub mod point {
         }
     }
 
+    impl Ord for Point {
+        fn cmp(&self, other: &Point) -> Ordering {
+            (self.y, self.x).cmp(&(other.y, other.x))
+        }
+    }
+
+    impl PartialOrd for Point {
+        fn partial_cmp(&self, other: &Point) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
     impl fmt::Debug for Point {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             write!(f, "Point(x={}, y={})", self.x, self.y)
@@ -85,27 +377,33 @@ pub mod point {
 
     impl Abs for usize {
         fn abs_diff(&self, diff: &Self) -> usize {
-            if self < diff { diff - self } else { self - diff }
+            (*self).abs_diff(*diff)
         }
     }
 
-    impl Abs for i32 {
-        fn abs_diff(&self, diff: &Self) -> usize {
-            (self - diff).abs() as usize
-        }
+    macro_rules! signed_abs {
+        ($($t:ty),+) => {
+            $(
+                impl Abs for $t {
+                    fn abs_diff(&self, diff: &Self) -> usize {
+                        (*self).abs_diff(*diff) as usize
+                    }
+                }
+            )+
+        };
     }
 
+    signed_abs!(i16, i32, i64, isize);
+
     impl<T: Sub + Abs> Point3d<T> {
         pub fn new(x: T, y: T, z: T) -> Point3d<T> {
             Point3d { x, y, z }
         }
         
         pub fn distance(&self, to: &Point3d<T>) -> usize {
-            (
-                self.x.abs_diff(&to.x) +
-                self.y.abs_diff(&to.y) +
-                self.z.abs_diff(&to.z)
-            ) as usize                
+            self.x.abs_diff(&to.x) +
+            self.y.abs_diff(&to.y) +
+            self.z.abs_diff(&to.z)
         }
     }
 
@@ -121,69 +419,2974 @@ pub mod point {
         }
     }
 
+    #[derive(Debug, PartialEq)]
+    pub enum ParsePointError {
+        Value(String),
+        Dimensions(usize, usize),
+    }
+
+    impl fmt::Display for ParsePointError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                ParsePointError::Value(value) => write!(f, "Invalid coordinate {:?}", value),
+                ParsePointError::Dimensions(expected, found) =>
+                    write!(f, "Expected {} coordinates, found {}", expected, found),
+            }
+        }
+    }
+
+    impl Error for ParsePointError {}
+
+    /// A point with `D` coordinates, e.g. `PointN<i32, 4>` for day 25.
+    #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+    pub struct PointN<T, const D: usize> {
+        pub coords: [T; D],
+    }
+
+    impl<T, const D: usize> PointN<T, D> {
+        pub fn new(coords: [T; D]) -> PointN<T, D> {
+            PointN { coords }
+        }
+    }
+
+    impl<T: Copy> PointN<T, 2> {
+        pub fn x(&self) -> T {
+            self.coords[0]
+        }
+
+        pub fn y(&self) -> T {
+            self.coords[1]
+        }
+    }
+
+    impl<T: Copy> PointN<T, 3> {
+        pub fn x(&self) -> T {
+            self.coords[0]
+        }
+
+        pub fn y(&self) -> T {
+            self.coords[1]
+        }
+
+        pub fn z(&self) -> T {
+            self.coords[2]
+        }
+    }
+
+    impl<T: Default + Copy, const D: usize> PointN<T, D> {
+        pub fn origin() -> PointN<T, D> {
+            PointN { coords: [T::default(); D] }
+        }
+    }
+
+    impl<T: Abs, const D: usize> PointN<T, D> {
+        fn diffs<'a>(&'a self, to: &'a PointN<T, D>) -> impl Iterator<Item=usize> + 'a {
+            self.coords.iter().zip(to.coords.iter()).map(|(a, b)| a.abs_diff(b))
+        }
+
+        /// Manhattan distance, the sum of the differences on each axis.
+        pub fn distance(&self, to: &PointN<T, D>) -> usize {
+            self.diffs(to).sum()
+        }
+
+        /// The largest difference on any one axis.
+        pub fn chebyshev(&self, to: &PointN<T, D>) -> usize {
+            self.diffs(to).max().unwrap_or(0)
+        }
+
+        pub fn euclidean(&self, to: &PointN<T, D>) -> f64 {
+            self.diffs(to).map(|d| (d * d) as f64).sum::<f64>().sqrt()
+        }
+    }
+
+    impl<T, const D: usize> PointN<T, D>
+        where T: Copy + Add<Output=T> + Sub<Output=T> + From<i8> {
+
+        fn offset(&self, steps: [i8; D]) -> PointN<T, D> {
+            let mut coords = self.coords;
+            for (coord, &step) in coords.iter_mut().zip(steps.iter()) {
+                *coord = *coord + T::from(step);
+            }
+            PointN { coords }
+        }
+
+        /// The `2 * D` points one step along a single axis, in reading order.
+        pub fn neighbors(&self) -> impl Iterator<Item=PointN<T, D>> {
+            let mut steps = Vec::with_capacity(2 * D);
+            for axis in (0..D).rev() {
+                let mut step = [0; D];
+                step[axis] = -1;
+                steps.push(step);
+            }
+            for axis in 0..D {
+                let mut step = [0; D];
+                step[axis] = 1;
+                steps.push(step);
+            }
+            let origin = *self;
+            steps.into_iter().map(move |step| origin.offset(step))
+        }
+
+        /// All `3^D - 1` touching points, diagonals included, in reading order.
+        pub fn neighbors_diagonal(&self) -> impl Iterator<Item=PointN<T, D>> {
+            let count = 3usize.pow(D as u32);
+            let origin = *self;
+            (0..count).filter(move |&n| n != count / 2).map(move |mut n| {
+                let mut step = [0; D];
+                for s in step.iter_mut() {
+                    *s = (n % 3) as i8 - 1;
+                    n /= 3;
+                }
+                origin.offset(step)
+            })
+        }
+    }
+
+    impl<T: Copy + Add<Output=T>, const D: usize> Add for PointN<T, D> {
+        type Output = PointN<T, D>;
+
+        fn add(mut self, other: PointN<T, D>) -> PointN<T, D> {
+            for (a, &b) in self.coords.iter_mut().zip(other.coords.iter()) {
+                *a = *a + b;
+            }
+            self
+        }
+    }
+
+    impl<T: Copy + Sub<Output=T>, const D: usize> Sub for PointN<T, D> {
+        type Output = PointN<T, D>;
+
+        fn sub(mut self, other: PointN<T, D>) -> PointN<T, D> {
+            for (a, &b) in self.coords.iter_mut().zip(other.coords.iter()) {
+                *a = *a - b;
+            }
+            self
+        }
+    }
+
+    impl<T: Copy + Neg<Output=T>, const D: usize> Neg for PointN<T, D> {
+        type Output = PointN<T, D>;
+
+        fn neg(mut self) -> PointN<T, D> {
+            for a in self.coords.iter_mut() {
+                *a = -*a;
+            }
+            self
+        }
+    }
+
+    impl<T: Copy + Mul<Output=T>, const D: usize> Mul<T> for PointN<T, D> {
+        type Output = PointN<T, D>;
+
+        fn mul(mut self, scale: T) -> PointN<T, D> {
+            for a in self.coords.iter_mut() {
+                *a = *a * scale;
+            }
+            self
+        }
+    }
+
+    /// Reading order: the last axis is compared first, so 2D points sort
+    /// top-to-bottom, then left-to-right.
+    impl<T: Ord, const D: usize> Ord for PointN<T, D> {
+        fn cmp(&self, other: &PointN<T, D>) -> Ordering {
+            self.coords.iter().rev().cmp(other.coords.iter().rev())
+        }
+    }
+
+    impl<T: Ord, const D: usize> PartialOrd for PointN<T, D> {
+        fn partial_cmp(&self, other: &PointN<T, D>) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl<T: FromStr + Default + Copy, const D: usize> FromStr for PointN<T, D> {
+        type Err = ParsePointError;
+
+        fn from_str(text: &str) -> Result<PointN<T, D>, ParsePointError> {
+            let values: Vec<&str> = text.trim().split(',').collect();
+            if values.len() != D {
+                return Err(ParsePointError::Dimensions(D, values.len()));
+            }
+            let mut coords = [T::default(); D];
+            for (coord, value) in coords.iter_mut().zip(values) {
+                *coord = value.trim().parse::<T>()
+                    .map_err(|_| ParsePointError::Value(value.to_string()))?;
+            }
+            Ok(PointN { coords })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn point_n_metrics_and_order() {
+            let p: PointN<i32, 2> = "1,2".parse().unwrap();
+            let q = PointN::new([4, -2]);
+            assert_eq!((p.x(), p.y()), (1, 2));
+            assert_eq!(PointN::new([4, 5, 6]).z(), 6);
+            assert_eq!(p.distance(&q), 7);
+            assert_eq!(p.chebyshev(&q), 4);
+            assert_eq!(p.euclidean(&q), 5.0);
+            assert_eq!(-(p - q) * 2, PointN::new([6, -8]));
+            assert!(PointN::new([5, 1]) < PointN::new([0, 2]));
+            let around: Vec<_> = PointN::<i32, 2>::origin().neighbors().collect();
+            assert_eq!(around, vec![
+                PointN::new([0, -1]), PointN::new([-1, 0]),
+                PointN::new([1, 0]), PointN::new([0, 1]),
+            ]);
+            let mut diagonal: Vec<_> = p.neighbors_diagonal().collect();
+            assert_eq!(diagonal.len(), 8);
+            assert!(diagonal.windows(2).all(|w| w[0] < w[1]));
+            diagonal.retain(|n| n.distance(&p) == 1);
+            assert_eq!(diagonal, p.neighbors().collect::<Vec<_>>());
+            assert_eq!("1,2,3".parse::<PointN<i32, 2>>(), Err(ParsePointError::Dimensions(2, 3)));
+            assert_eq!(" 3, -4".parse::<Point>().map(|p| (p.x, p.y)), Ok((3, -4)));
+            assert_eq!("5".parse::<Point>().map(|p| p.x), Err(ParsePointError::Dimensions(2, 1)));
+        }
+    }
 }
 
-pub mod op_code {
-    use std::collections::HashMap;
+pub mod grid {
+    use std::fmt;
+    use std::ops::{Index, IndexMut};
+    use std::str::FromStr;
 
-    pub struct Instruction {
-        pub name: String,
-        pub reg: [usize; 3],
+    use super::point::PointN;
+
+    pub type Pos = PointN<i32, 2>;
+
+    #[derive(Debug, PartialEq)]
+    pub enum ParseGridError {
+        Empty,
+        Ragged { line: usize, expected: usize, found: usize },
     }
 
-    impl Instruction {
-        pub fn new(line: &str) -> Instruction {
-            let fields: Vec<&str> = line.split(' ').collect();
-            Instruction {
-                name: fields[0].to_string(),
-                reg: [
-                    fields[1].parse::<usize>().unwrap(),
-                    fields[2].parse::<usize>().unwrap(),
-                    fields[3].parse::<usize>().unwrap(),
-                ],
+    impl fmt::Display for ParseGridError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                ParseGridError::Empty => write!(f, "Grid has no rows"),
+                ParseGridError::Ragged { line, expected, found } =>
+                    write!(f, "Line {} has {} cells, expected {}", line, found, expected),
             }
         }
     }
 
-    type Code = [usize; 3];
-    type Val = [usize; 6];
-    type Op = dyn Fn(Code, Val) -> Val;
+    /// A dense, row-major 2D map with the origin in the upper left.
+    #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+    pub struct Grid<T> {
+        width: usize,
+        height: usize,
+        cells: Vec<T>,
+    }
 
-    macro_rules! opcode_map(
-        { $($key:ident : $value:expr),+ } => {
-            {
-                let mut m: HashMap<String, &Op> = HashMap::new();
-                $(
-                    fn $key(code: Code, reg: Val) -> Val {
-                        let mut new_val = reg.clone();
-                        new_val[code[2]] = $value(code, reg);
-                        new_val
-                    }
-                    m.insert(stringify!($key).to_string(), &$key);
-                )+
-                m
+    impl<T: Clone> Grid<T> {
+        pub fn new(width: usize, height: usize, fill: T) -> Grid<T> {
+            Grid { width, height, cells: vec![fill; width * height] }
+        }
+    }
+
+    impl<T> Grid<T> {
+        pub fn width(&self) -> usize {
+            self.width
+        }
+
+        pub fn height(&self) -> usize {
+            self.height
+        }
+
+        pub fn contains(&self, pos: Pos) -> bool {
+            let [x, y] = pos.coords;
+            0 <= x && (x as usize) < self.width && 0 <= y && (y as usize) < self.height
+        }
+
+        fn index_of(&self, pos: Pos) -> Option<usize> {
+            if self.contains(pos) {
+                Some(pos.coords[1] as usize * self.width + pos.coords[0] as usize)
+            } else {
+                None
+            }
+        }
+
+        pub fn get(&self, pos: Pos) -> Option<&T> {
+            self.index_of(pos).map(|i| &self.cells[i])
+        }
+
+        pub fn get_mut(&mut self, pos: Pos) -> Option<&mut T> {
+            self.index_of(pos).map(move |i| &mut self.cells[i])
+        }
+
+        /// Every position in reading order.
+        pub fn positions(&self) -> impl Iterator<Item=Pos> {
+            let width = self.width;
+            (0..self.cells.len()).map(move |i| Pos::new([(i % width) as i32, (i / width) as i32]))
+        }
+
+        pub fn iter(&self) -> impl Iterator<Item=(Pos, &T)> {
+            self.positions().zip(self.cells.iter())
+        }
+
+        /// In-bounds orthogonal neighbors of `pos`, in reading order.
+        pub fn neighbors(&self, pos: Pos) -> impl Iterator<Item=(Pos, &T)> {
+            pos.neighbors().filter_map(move |p| self.get(p).map(|v| (p, v)))
+        }
+
+        /// In-bounds neighbors of `pos` including diagonals, in reading order.
+        pub fn neighbors_diagonal(&self, pos: Pos) -> impl Iterator<Item=(Pos, &T)> {
+            pos.neighbors_diagonal().filter_map(move |p| self.get(p).map(|v| (p, v)))
+        }
+
+        pub fn rows(&self) -> impl Iterator<Item=&[T]> {
+            self.cells.chunks(self.width.max(1))
+        }
+
+        pub fn column(&self, x: usize) -> impl Iterator<Item=&T> {
+            self.cells.iter().skip(x).step_by(self.width.max(1)).take(self.height)
+        }
+
+        pub fn columns(&self) -> impl Iterator<Item=impl Iterator<Item=&T>> {
+            (0..self.width).map(move |x| self.column(x))
+        }
+
+        pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+            Grid { width: self.width, height: self.height, cells: self.cells.iter().map(f).collect() }
+        }
+
+        /// A window onto `width` by `height` cells starting at `upper_left`,
+        /// clipped to the grid.
+        pub fn view(&self, upper_left: Pos, width: usize, height: usize) -> View<'_, T> {
+            let [x, y] = upper_left.coords;
+            let x = x.clamp(0, self.width as i32) as usize;
+            let y = y.clamp(0, self.height as i32) as usize;
+            View {
+                grid: self,
+                x,
+                y,
+                width: width.min(self.width - x),
+                height: height.min(self.height - y),
+            }
+        }
+
+        pub fn render<F: Fn(&T) -> char>(&self, f: F) -> String {
+            self.view(Pos::origin(), self.width, self.height).render(f)
+        }
+    }
+
+    impl<T> Index<Pos> for Grid<T> {
+        type Output = T;
+
+        fn index(&self, pos: Pos) -> &T {
+            self.get(pos).unwrap_or_else(|| panic!("{:?} is outside the grid", pos))
+        }
+    }
+
+    impl<T> IndexMut<Pos> for Grid<T> {
+        fn index_mut(&mut self, pos: Pos) -> &mut T {
+            match self.index_of(pos) {
+                Some(i) => &mut self.cells[i],
+                None => panic!("{:?} is outside the grid", pos),
+            }
+        }
+    }
+
+    /// Parses ASCII art, one row per line. Every line must be the same width.
+    impl FromStr for Grid<char> {
+        type Err = ParseGridError;
+
+        fn from_str(text: &str) -> Result<Grid<char>, ParseGridError> {
+            let lines: Vec<&str> = text.trim_end_matches('\n').lines().collect();
+            let width = match lines.first() {
+                Some(line) => line.chars().count(),
+                None => return Err(ParseGridError::Empty),
+            };
+            let mut cells = Vec::with_capacity(width * lines.len());
+            for (n, line) in lines.iter().enumerate() {
+                let found = line.chars().count();
+                if found != width {
+                    return Err(ParseGridError::Ragged { line: n + 1, expected: width, found });
+                }
+                cells.extend(line.chars());
+            }
+            Ok(Grid { width, height: lines.len(), cells })
+        }
+    }
+
+    impl fmt::Display for Grid<char> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.render(|&c| c))
+        }
+    }
+
+    pub struct View<'a, T> {
+        grid: &'a Grid<T>,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    }
+
+    impl<'a, T> View<'a, T> {
+        pub fn width(&self) -> usize {
+            self.width
+        }
+
+        pub fn height(&self) -> usize {
+            self.height
+        }
+
+        /// Looks up `pos` relative to the view's upper left corner.
+        pub fn get(&self, pos: Pos) -> Option<&'a T> {
+            let [x, y] = pos.coords;
+            if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+                return None;
+            }
+            self.grid.get(Pos::new([(self.x + x as usize) as i32, (self.y + y as usize) as i32]))
+        }
+
+        pub fn rows(&self) -> impl Iterator<Item=&'a [T]> {
+            let (x, width) = (self.x, self.width);
+            self.grid.rows().skip(self.y).take(self.height).map(move |row| &row[x..x + width])
+        }
+
+        pub fn render<F: Fn(&T) -> char>(&self, f: F) -> String {
+            self.rows()
+                .map(|row| row.iter().map(&f).collect::<String>())
+                .collect::<Vec<String>>()
+                .join("\n")
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn parse_query_and_render() {
+            let grid: Grid<char> = "#.#\n.|.\n##.\n".parse().unwrap();
+            assert_eq!((grid.width(), grid.height()), (3, 3));
+            assert_eq!(grid[Pos::new([1, 1])], '|');
+            assert_eq!(grid.get(Pos::new([3, 0])), None);
+            assert_eq!(grid.neighbors(Pos::new([0, 0])).count(), 2);
+            assert_eq!(grid.neighbors_diagonal(Pos::new([1, 1]))
+                .filter(|&(_, &c)| c == '#').count(), 4);
+            assert_eq!(grid.column(0).collect::<String>(), "#.#");
+            assert_eq!(grid.view(Pos::new([1, 1]), 5, 5).render(|&c| c), "|.\n#.");
+            assert_eq!(grid.to_string(), "#.#\n.|.\n##.");
+            assert_eq!("##\n#".parse::<Grid<char>>(),
+                       Err(ParseGridError::Ragged { line: 2, expected: 2, found: 1 }));
+        }
+    }
+}
+
+pub mod search {
+    use std::cmp::Reverse;
+    use std::collections::{BinaryHeap, HashMap};
+    use std::fmt;
+    use std::hash::Hash;
+    use std::ops::RangeInclusive;
+
+    use crate::grid::{Grid, Pos};
+
+    /// A state space to search. Where several goals are equally near, `bfs`
+    /// returns the smallest by the state's `Ord`, so with reading-ordered
+    /// points it finds the first nearest goal in reading order. Only the goal
+    /// is chosen this way: the path to it is any shortest path, so a caller
+    /// wanting the first step in reading order searches again from the goal
+    /// back to the cells next to the start.
+    pub trait Graph {
+        type State: Clone + Ord + Hash;
+
+        /// States reachable in one move, with the cost of that move.
+        fn neighbors(&self, state: &Self::State) -> Vec<(Self::State, usize)>;
+
+        /// A lower bound on the remaining cost to a goal, used by `astar`.
+        fn heuristic(&self, _state: &Self::State) -> usize {
+            0
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct Path<S> {
+        pub cost: usize,
+        pub states: Vec<S>,
+    }
+
+    /// Where a search records how it reached each state. A state that is
+    /// its own parent is where the search started.
+    pub trait Parents<S> {
+        fn parent(&self, state: &S) -> Option<&S>;
+        fn insert(&mut self, state: S, parent: S);
+    }
+
+    impl<S: Eq + Hash> Parents<S> for HashMap<S, S> {
+        fn parent(&self, state: &S) -> Option<&S> {
+            self.get(state)
+        }
+
+        fn insert(&mut self, state: S, parent: S) {
+            HashMap::insert(self, state, parent);
+        }
+    }
+
+    /// Parents of positions kept in a grid, which is much faster than
+    /// hashing when most of a dense map gets searched.
+    impl Parents<Pos> for Grid<Option<Pos>> {
+        fn parent(&self, state: &Pos) -> Option<&Pos> {
+            self.get(*state)?.as_ref()
+        }
+
+        fn insert(&mut self, state: Pos, parent: Pos) {
+            self[state] = Some(parent);
+        }
+    }
+
+    fn reconstruct<S: Clone + Eq, P: Parents<S>>(parents: &P, goal: S, cost: usize) -> Path<S> {
+        let mut states = vec![goal];
+        while let Some(parent) = parents.parent(states.last().unwrap()) {
+            if parent == states.last().unwrap() {
+                break;
+            }
+            states.push(parent.clone());
+        }
+        states.reverse();
+        Path { cost, states }
+    }
+
+    /// Breadth first search ignoring move costs. Each layer is expanded in
+    /// order, so the goal returned is the smallest of the nearest goals.
+    pub fn bfs<G, F>(graph: &G, start: G::State, is_goal: F) -> Option<Path<G::State>>
+        where G: Graph, F: Fn(&G::State) -> bool {
+        bfs_with(graph, start, is_goal, HashMap::new())
+    }
+
+    /// `bfs`, recording parents in `parents`, which must start out empty.
+    pub fn bfs_with<G, F, P>(graph: &G, start: G::State, is_goal: F, mut parents: P) -> Option<Path<G::State>>
+        where G: Graph, F: Fn(&G::State) -> bool, P: Parents<G::State> {
+        parents.insert(start.clone(), start.clone());
+        let mut layer = vec![start];
+        let mut depth = 0;
+        while !layer.is_empty() {
+            layer.sort();
+            if let Some(goal) = layer.iter().find(|s| is_goal(s)) {
+                return Some(reconstruct(&parents, goal.clone(), depth));
+            }
+            depth += 1;
+            let mut next = Vec::new();
+            for state in layer {
+                for (neighbor, _) in graph.neighbors(&state) {
+                    if parents.parent(&neighbor).is_none() {
+                        parents.insert(neighbor.clone(), state.clone());
+                        next.push(neighbor);
+                    }
+                }
+            }
+            layer = next;
+        }
+        None
+    }
+
+    /// Number of moves from `start` to every reachable state.
+    pub fn distances<G: Graph>(graph: &G, start: G::State) -> HashMap<G::State, usize> {
+        let mut seen = HashMap::new();
+        seen.insert(start.clone(), 0);
+        let mut layer = vec![start];
+        let mut depth = 0;
+        while !layer.is_empty() {
+            depth += 1;
+            let mut next = Vec::new();
+            for state in layer {
+                for (neighbor, _) in graph.neighbors(&state) {
+                    if !seen.contains_key(&neighbor) {
+                        seen.insert(neighbor.clone(), depth);
+                        next.push(neighbor);
+                    }
+                }
+            }
+            layer = next;
+        }
+        seen
+    }
+
+    fn best_first<G, F>(graph: &G, start: G::State, is_goal: F, guided: bool)
+        -> Option<Path<G::State>>
+        where G: Graph, F: Fn(&G::State) -> bool {
+        let estimate = |s: &G::State| if guided { graph.heuristic(s) } else { 0 };
+        let mut parents: HashMap<G::State, G::State> = HashMap::new();
+        let mut costs: HashMap<G::State, usize> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        costs.insert(start.clone(), 0);
+        queue.push(Reverse((estimate(&start), 0, start)));
+        while let Some(Reverse((_, cost, state))) = queue.pop() {
+            if costs.get(&state).is_some_and(|&c| c < cost) {
+                continue;
+            }
+            if is_goal(&state) {
+                return Some(reconstruct(&parents, state, cost));
+            }
+            for (neighbor, step) in graph.neighbors(&state) {
+                let new_cost = cost + step;
+                if costs.get(&neighbor).is_none_or(|&c| new_cost < c) {
+                    costs.insert(neighbor.clone(), new_cost);
+                    parents.insert(neighbor.clone(), state.clone());
+                    queue.push(Reverse((new_cost + estimate(&neighbor), new_cost, neighbor)));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn dijkstra<G, F>(graph: &G, start: G::State, is_goal: F) -> Option<Path<G::State>>
+        where G: Graph, F: Fn(&G::State) -> bool {
+        best_first(graph, start, is_goal, false)
+    }
+
+    /// Like `dijkstra`, but guided by `Graph::heuristic`, which must never
+    /// overestimate for the result to be a cheapest path.
+    pub fn astar<G, F>(graph: &G, start: G::State, is_goal: F) -> Option<Path<G::State>>
+        where G: Graph, F: Fn(&G::State) -> bool {
+        best_first(graph, start, is_goal, true)
+    }
+
+    /// The lowest value in `range` that `test` accepts, with what it returned,
+    /// assuming it accepts every value above that too, or `Err` with the top
+    /// of the range if it accepts none. Probes upward in doubling steps, then
+    /// bisects the gap, so a low answer is found quickly.
+    pub fn lowest<T, F>(range: RangeInclusive<usize>, mut test: F) -> Result<(usize, T), usize>
+        where F: FnMut(usize) -> Option<T> {
+        let (mut low, high) = range.into_inner();
+        let mut step = 1;
+        let mut best = None;
+        while best.is_none() && low <= high {
+            let probe = low.saturating_add(step - 1).min(high);
+            match test(probe) {
+                Some(result) => best = Some((probe, result)),
+                None if probe == high => return Err(high),
+                None => {
+                    low = probe + 1;
+                    step *= 2;
+                }
+            }
+        }
+        let (mut high, mut found) = best.ok_or(high)?;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match test(mid) {
+                Some(result) => {
+                    high = mid;
+                    found = result;
+                }
+                None => low = mid + 1,
+            }
+        }
+        Ok((high, found))
+    }
+
+    /// A `lowest` answer for display: the answer, or the bound it gave up at.
+    pub struct Lowest<T>(pub Result<T, usize>);
+
+    impl<T: fmt::Display> fmt::Display for Lowest<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match &self.0 {
+                Ok(answer) => answer.fmt(f),
+                Err(high) => write!(f, "Nothing up to {} works", high),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        struct Maze(Grid<char>);
+
+        impl Graph for Maze {
+            type State = Pos;
+
+            fn neighbors(&self, state: &Pos) -> Vec<(Pos, usize)> {
+                self.0.neighbors(*state)
+                    .filter(|&(_, &c)| c != '#')
+                    .map(|(p, &c)| (p, if c == '~' { 5 } else { 1 }))
+                    .collect()
+            }
+
+            fn heuristic(&self, state: &Pos) -> usize {
+                state.distance(&Pos::new([4, 2]))
+            }
+        }
+
+        #[test]
+        fn searches_agree_on_maze() {
+            let maze = Maze(".....\n.#~#.\n.....".parse().unwrap());
+            let start = Pos::new([0, 0]);
+            let goal = |p: &Pos| *p == Pos::new([4, 2]);
+            let path = bfs(&maze, start, goal).unwrap();
+            assert_eq!(path.cost, 6);
+            assert_eq!(path.states[1], Pos::new([1, 0]));
+            let either = |p: &Pos| *p == Pos::new([0, 2]) || *p == Pos::new([2, 0]);
+            assert_eq!(bfs(&maze, start, either).unwrap().states.last(), Some(&Pos::new([2, 0])));
+            let grid = Grid::new(5, 3, None);
+            assert_eq!(bfs_with(&maze, start, goal, grid), Some(path));
+            assert_eq!(dijkstra(&maze, start, goal).unwrap().cost, 6);
+            assert_eq!(astar(&maze, start, goal), dijkstra(&maze, start, goal));
+            assert_eq!(distances(&maze, start)[&Pos::new([2, 1])], 3);
+        }
+
+        #[test]
+        fn lowest_finds_threshold() {
+            for threshold in 0..40 {
+                let mut tries = 0;
+                let found = lowest(0..=40, |n| {
+                    tries += 1;
+                    Some(n * 2).filter(|_| n >= threshold)
+                });
+                assert_eq!(found, Ok((threshold, threshold * 2)));
+                assert!(tries <= 12);
+            }
+            let hopeless = lowest(3..=9, |_| None::<u32>);
+            assert_eq!(hopeless, Err(9));
+            assert_eq!(Lowest(hopeless.map(|(_, n)| n)).to_string(), "Nothing up to 9 works");
+            assert_eq!(Lowest(Ok(51)).to_string(), "51");
+        }
+    }
+}
+
+pub mod cycle {
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    /// A sequence that enters a loop of `length` states after `start` steps.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Cycle {
+        pub start: usize,
+        pub length: usize,
+    }
+
+    impl Cycle {
+        /// The earliest step whose state is the same as the state at step `n`.
+        pub fn equivalent(&self, n: usize) -> usize {
+            if n < self.start {
+                n
+            } else {
+                self.start + (n - self.start) % self.length
+            }
+        }
+    }
+
+    pub fn floyd<T, F>(start: &T, mut step: F) -> Cycle
+        where T: Clone + PartialEq, F: FnMut(&T) -> T {
+        let mut tortoise = step(start);
+        let mut hare = step(&tortoise);
+        while tortoise != hare {
+            tortoise = step(&tortoise);
+            hare = step(&hare);
+            hare = step(&hare);
+        }
+        let mut first = 0;
+        tortoise = start.clone();
+        while tortoise != hare {
+            tortoise = step(&tortoise);
+            hare = step(&hare);
+            first += 1;
+        }
+        let mut length = 1;
+        hare = step(&tortoise);
+        while tortoise != hare {
+            hare = step(&hare);
+            length += 1;
+        }
+        Cycle { start: first, length }
+    }
+
+    pub fn brent<T, F>(start: &T, mut step: F) -> Cycle
+        where T: Clone + PartialEq, F: FnMut(&T) -> T {
+        let mut power = 1;
+        let mut length = 1;
+        let mut tortoise = start.clone();
+        let mut hare = step(start);
+        while tortoise != hare {
+            if power == length {
+                tortoise = hare.clone();
+                power *= 2;
+                length = 0;
+            }
+            hare = step(&hare);
+            length += 1;
+        }
+        tortoise = start.clone();
+        hare = start.clone();
+        for _ in 0..length {
+            hare = step(&hare);
+        }
+        let mut first = 0;
+        while tortoise != hare {
+            tortoise = step(&tortoise);
+            hare = step(&hare);
+            first += 1;
+        }
+        Cycle { start: first, length }
+    }
+
+    /// Every state visited, in order, up to the first repeat.
+    #[derive(Debug)]
+    pub struct History<T> {
+        pub states: Vec<T>,
+        pub cycle: Option<Cycle>,
+    }
+
+    impl<T> History<T> {
+        /// The state after `n` steps, if it was visited or is known from the cycle.
+        pub fn nth(&self, n: usize) -> Option<&T> {
+            match self.cycle {
+                Some(cycle) => self.states.get(cycle.equivalent(n)),
+                None => self.states.get(n),
+            }
+        }
+    }
+
+    fn walk<T, K, F, G>(start: T, mut step: F, key: G, limit: Option<usize>) -> History<T>
+        where T: Clone, K: Hash + Eq, F: FnMut(&T) -> T, G: Fn(&T) -> K {
+        let mut seen: HashMap<K, usize> = HashMap::new();
+        let mut states = Vec::new();
+        let mut state = start;
+        loop {
+            if let Some(&first) = seen.get(&key(&state)) {
+                let cycle = Cycle { start: first, length: states.len() - first };
+                return History { states, cycle: Some(cycle) };
+            }
+            seen.insert(key(&state), states.len());
+            let next = step(&state);
+            states.push(state);
+            if limit.is_some_and(|n| states.len() > n) {
+                return History { states, cycle: None };
+            }
+            state = next;
+        }
+    }
+
+    /// Steps until a state repeats; never returns if the sequence does not loop.
+    pub fn detect<T, F>(start: T, step: F) -> History<T>
+        where T: Clone + Hash + Eq, F: FnMut(&T) -> T {
+        walk(start, step, T::clone, None)
+    }
+
+    /// Like `detect`, but states repeat when their keys are equal, so
+    /// `states[start + length]` is the first state that has the same key as
+    /// `states[start]` and is not itself stored.
+    pub fn detect_by<T, K, F, G>(start: T, step: F, key: G) -> History<T>
+        where T: Clone, K: Hash + Eq, F: FnMut(&T) -> T, G: Fn(&T) -> K {
+        walk(start, step, key, None)
+    }
+
+    /// The state after `n` steps, skipping ahead once the sequence loops.
+    pub fn fast_forward<T, F>(start: T, step: F, n: usize) -> T
+        where T: Clone + Hash + Eq, F: FnMut(&T) -> T {
+        walk(start, step, T::clone, Some(n)).nth(n).unwrap().clone()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn step(x: &u32) -> u32 {
+            (x * x + 1) % 255
+        }
+
+        #[test]
+        fn detectors_agree() {
+            let history = detect(3, step);
+            let cycle = history.cycle.unwrap();
+            assert_eq!(floyd(&3, step), cycle);
+            assert_eq!(brent(&3, step), cycle);
+            assert_eq!(history.states.len(), cycle.start + cycle.length);
+            for n in [0, 5, 17, 1_000] {
+                let naive = (0..n).fold(3, |x, _| step(&x));
+                assert_eq!(fast_forward(3, step, n), naive);
+                assert_eq!(history.nth(n), Some(&naive));
+            }
+            assert_eq!(history.nth(1_000_000_000), Some(&fast_forward(3, step, 1_000_000_000)));
+        }
+    }
+}
+
+pub mod op_code {
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+    use std::fmt;
+    use std::io::{self, BufRead, Write};
+    use std::ops::{BitAnd, BitOr};
+    use std::str::FromStr;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub enum Opcode {
+        Addr, Addi, Mulr, Muli, Banr, Bani, Borr, Bori,
+        Setr, Seti, Gtir, Gtri, Gtrr, Eqir, Eqri, Eqrr,
+    }
+
+    use Opcode::*;
+
+    impl Opcode {
+        pub const ALL: [Opcode; 16] = [
+            Addr, Addi, Mulr, Muli, Banr, Bani, Borr, Bori,
+            Setr, Seti, Gtir, Gtri, Gtrr, Eqir, Eqri, Eqrr,
+        ];
+
+        pub fn name(self) -> &'static str {
+            match self {
+                Addr => "addr", Addi => "addi", Mulr => "mulr", Muli => "muli",
+                Banr => "banr", Bani => "bani", Borr => "borr", Bori => "bori",
+                Setr => "setr", Seti => "seti", Gtir => "gtir", Gtri => "gtri",
+                Gtrr => "gtrr", Eqir => "eqir", Eqri => "eqri", Eqrr => "eqrr",
+            }
+        }
+
+        /// Whether arguments `a` and `b` name registers rather than values.
+        pub fn register_args(self) -> [bool; 2] {
+            match self {
+                Addr | Mulr | Banr | Borr | Gtrr | Eqrr => [true, true],
+                Addi | Muli | Bani | Bori | Gtri | Eqri | Setr => [true, false],
+                Gtir | Eqir => [false, true],
+                Seti => [false, false],
+            }
+        }
+
+        fn symbol(self) -> Option<&'static str> {
+            match self {
+                Addr | Addi => Some("+"),
+                Mulr | Muli => Some("*"),
+                Banr | Bani => Some("&"),
+                Borr | Bori => Some("|"),
+                Gtir | Gtri | Gtrr => Some(">"),
+                Eqir | Eqri | Eqrr => Some("=="),
+                Setr | Seti => None,
+            }
+        }
+
+        /// The value this opcode writes to register `c`.
+        pub fn eval<W: Word>(self, a: usize, b: usize, r: &[W]) -> W {
+            let imm = W::from_operand;
+            let flag = |set: bool| imm(set as usize);
+            match self {
+                Addr => r[a].wrapping_add(r[b]),
+                Addi => r[a].wrapping_add(imm(b)),
+                Mulr => r[a].wrapping_mul(r[b]),
+                Muli => r[a].wrapping_mul(imm(b)),
+                Banr => r[a] & r[b],
+                Bani => r[a] & imm(b),
+                Borr => r[a] | r[b],
+                Bori => r[a] | imm(b),
+                Setr => r[a],
+                Seti => imm(a),
+                Gtir => flag(imm(a) > r[b]),
+                Gtri => flag(r[a] > imm(b)),
+                Gtrr => flag(r[a] > r[b]),
+                Eqir => flag(imm(a) == r[b]),
+                Eqri => flag(r[a] == imm(b)),
+                Eqrr => flag(r[a] == r[b]),
+            }
+        }
+    }
+
+    /// A register value. The opcodes are defined once over this, so any
+    /// register width and count runs the same semantics.
+    pub trait Word: Copy + Ord + BitAnd<Output=Self> + BitOr<Output=Self> {
+        /// An immediate operand as a register value, truncated to fit.
+        fn from_operand(value: usize) -> Self;
+        fn wrapping_add(self, other: Self) -> Self;
+        fn wrapping_mul(self, other: Self) -> Self;
+    }
+
+    macro_rules! word {
+        ($($word:ty),*) => ($(
+            impl Word for $word {
+                fn from_operand(value: usize) -> $word {
+                    value as $word
+                }
+
+                fn wrapping_add(self, other: $word) -> $word {
+                    <$word>::wrapping_add(self, other)
+                }
+
+                fn wrapping_mul(self, other: $word) -> $word {
+                    <$word>::wrapping_mul(self, other)
+                }
+            }
+        )*)
+    }
+
+    word!(u32, u64, usize);
+
+    /// What `addr`, `addi`, `mulr` and `muli` do when a result overflows.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum Arithmetic {
+        #[default]
+        Wrapping,
+        /// Stops the program with `VmError::Overflow`.
+        Checked,
+        /// Wraps at the given number of bits, for every value written. Widths
+        /// of `usize::BITS` or more behave like `Wrapping`.
+        Bits(u32),
+    }
+
+    impl Arithmetic {
+        /// The value `op` writes to register `c`, or None if it overflows.
+        pub fn eval(self, op: Opcode, a: usize, b: usize, r: &[usize]) -> Option<usize> {
+            let y = || if op.register_args()[1] { r[b] } else { b };
+            let value = match (self, op) {
+                (Arithmetic::Checked, Addr) | (Arithmetic::Checked, Addi) => r[a].checked_add(y())?,
+                (Arithmetic::Checked, Mulr) | (Arithmetic::Checked, Muli) => r[a].checked_mul(y())?,
+                _ => op.eval(a, b, r),
+            };
+            let bits = match self {
+                Arithmetic::Bits(bits) => bits,
+                _ => usize::BITS,
+            };
+            match bits < usize::BITS {
+                true => Some(value & ((1 << bits) - 1)),
+                false => Some(value),
+            }
+        }
+    }
+
+    impl fmt::Display for Arithmetic {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Arithmetic::Wrapping => write!(f, "wrapping"),
+                Arithmetic::Checked => write!(f, "checked"),
+                Arithmetic::Bits(bits) => write!(f, "bits:{}", bits),
+            }
+        }
+    }
+
+    impl FromStr for Arithmetic {
+        type Err = String;
+
+        /// Parses `wrapping`, `checked` or `bits:N`, as `Display` writes them.
+        fn from_str(text: &str) -> Result<Arithmetic, String> {
+            let bits = text.strip_prefix("bits:").map(|bits| bits.parse::<u32>());
+            match (text, bits) {
+                ("wrapping", _) => Ok(Arithmetic::Wrapping),
+                ("checked", _) => Ok(Arithmetic::Checked),
+                (_, Some(Ok(bits))) if bits > 0 => Ok(Arithmetic::Bits(bits)),
+                _ => Err(format!("Unknown arithmetic {:?}, expected wrapping, checked or bits:N", text)),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct UnknownOpcode(pub String);
+
+    impl FromStr for Opcode {
+        type Err = UnknownOpcode;
+
+        fn from_str(name: &str) -> Result<Opcode, UnknownOpcode> {
+            Opcode::ALL.iter().copied()
+                .find(|op| op.name() == name)
+                .ok_or_else(|| UnknownOpcode(name.to_string()))
+        }
+    }
+
+    impl fmt::Display for Opcode {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.name())
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct Instruction {
+        pub op: Opcode,
+        pub args: [usize; 3],
+    }
+
+    impl Instruction {
+        pub fn new(op: Opcode, a: usize, b: usize, c: usize) -> Instruction {
+            Instruction { op, args: [a, b, c] }
+        }
+
+        pub fn execute<W: Word>(&self, registers: &mut [W]) {
+            let [a, b, c] = self.args;
+            registers[c] = self.op.eval(a, b, registers);
+        }
+
+        /// The registers this instruction reads or writes.
+        pub fn registers(&self) -> impl Iterator<Item=usize> + '_ {
+            let [reg_a, reg_b] = self.op.register_args();
+            let args = self.args;
+            IntoIterator::into_iter([reg_a, reg_b, true]).zip(args).filter(|&(is_reg, _)| is_reg).map(|(_, r)| r)
+        }
+    }
+
+    impl fmt::Display for Instruction {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{} {} {} {}", self.op, self.args[0], self.args[1], self.args[2])
+        }
+    }
+
+    /// Parse errors, with 1-based line numbers.
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum ParseProgramError {
+        Opcode { line: usize, name: String },
+        Value { line: usize, value: String },
+        Arguments { line: usize, found: usize },
+        Symbol { line: usize, name: String },
+        Duplicate { line: usize, name: String },
+        Reserved { line: usize, name: String },
+        Register { line: usize, register: usize, count: usize },
+    }
+
+    impl fmt::Display for ParseProgramError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                ParseProgramError::Opcode { line, name } =>
+                    write!(f, "Line {}: unknown opcode {:?}", line, name),
+                ParseProgramError::Value { line, value } =>
+                    write!(f, "Line {}: invalid value {:?}", line, value),
+                ParseProgramError::Arguments { line, found } =>
+                    write!(f, "Line {}: expected 3 arguments, found {}", line, found),
+                ParseProgramError::Symbol { line, name } =>
+                    write!(f, "Line {}: undefined symbol {:?}", line, name),
+                ParseProgramError::Duplicate { line, name } =>
+                    write!(f, "Line {}: {:?} is already defined", line, name),
+                ParseProgramError::Reserved { line, name } =>
+                    write!(f, "Line {}: {:?} reads as a number or register, not a name", line, name),
+                ParseProgramError::Register { line, register, count } =>
+                    write!(f, "Line {}: register {} is out of range for {} registers", line, register, count),
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Program {
+        pub ip: Option<usize>,
+        pub instructions: Vec<Instruction>,
+    }
+
+    impl FromStr for Program {
+        type Err = ParseProgramError;
+
+        fn from_str(text: &str) -> Result<Program, ParseProgramError> {
+            assemble(text)
+        }
+    }
+
+    struct Symbols {
+        ip: Option<usize>,
+        names: HashMap<String, usize>,
+    }
+
+    impl Symbols {
+        /// Names that `resolve` would read as a number or register first.
+        fn reserved(name: &str) -> bool {
+            let digits = |text: &str| !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit());
+            name == "ip" || digits(name) || name.strip_prefix('r').is_some_and(digits)
+        }
+
+        fn define(&mut self, name: &str, value: usize, line: usize) -> Result<(), ParseProgramError> {
+            if Symbols::reserved(name) {
+                return Err(ParseProgramError::Reserved { line, name: name.to_string() });
+            }
+            if self.names.insert(name.to_string(), value).is_some() {
+                return Err(ParseProgramError::Duplicate { line, name: name.to_string() });
+            }
+            Ok(())
+        }
+
+        fn register(&self, field: &str) -> Option<usize> {
+            match field {
+                "ip" => self.ip,
+                _ => field.strip_prefix('r').and_then(|n| n.parse().ok()),
+            }
+        }
+
+        fn resolve(&self, field: &str, line: usize) -> Result<usize, ParseProgramError> {
+            if let Some(value) = field.parse().ok().or_else(|| self.register(field)) {
+                return Ok(value);
+            }
+            let (name, offset) = match field.rfind(['+', '-']).filter(|&i| i > 0) {
+                Some(i) => (&field[..i], &field[i..]),
+                None => (field, "+0"),
+            };
+            let base = *self.names.get(name)
+                .ok_or_else(|| ParseProgramError::Symbol { line, name: name.to_string() })?;
+            offset.trim_start_matches('+').parse::<isize>().ok()
+                .and_then(|offset| base.checked_add_signed(offset))
+                .ok_or_else(|| ParseProgramError::Value { line, value: field.to_string() })
+        }
+    }
+
+    /// Assembles ElfCode. On top of plain `name a b c` lines this accepts `;`
+    /// comments, `label:` prefixes, `#reg name rN` aliases, the register names
+    /// `rN` and `ip`, and labels with an optional `+n` or `-n` offset as
+    /// arguments. Writing `n` to the ip register continues at `n + 1`, so
+    /// `seti loop-1 0 ip` jumps to `loop`.
+    pub fn assemble(text: &str) -> Result<Program, ParseProgramError> {
+        assemble_for(text, usize::MAX)
+    }
+
+    /// Assembles ElfCode for a machine with `count` registers, rejecting
+    /// register operands it does not have.
+    pub fn load(text: &str, count: usize) -> Result<Program, ParseProgramError> {
+        assemble_for(text, count)
+    }
+
+    fn assemble_for(text: &str, count: usize) -> Result<Program, ParseProgramError> {
+        let check = |register: usize, line: usize| match register < count {
+            true => Ok(register),
+            false => Err(ParseProgramError::Register { line, register, count }),
+        };
+        let mut symbols = Symbols { ip: None, names: HashMap::new() };
+        let mut lines = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line_no = n + 1;
+            let mut fields: Vec<&str> = line.split(';').next().unwrap().split_whitespace().collect();
+            while let Some(label) = fields.first().and_then(|f| f.strip_suffix(':')) {
+                symbols.define(label, lines.len(), line_no)?;
+                fields.remove(0);
+            }
+            match fields.as_slice() {
+                [] => (),
+                ["#ip", reg] => symbols.ip = Some(check(symbols.resolve(reg, line_no)?, line_no)?),
+                ["#reg", name, reg] => {
+                    let reg = symbols.resolve(reg, line_no)?;
+                    symbols.define(name, reg, line_no)?;
+                }
+                [name, args @ ..] => {
+                    let op = name.parse::<Opcode>().map_err(|UnknownOpcode(name)|
+                        ParseProgramError::Opcode { line: line_no, name })?;
+                    if args.len() != 3 {
+                        return Err(ParseProgramError::Arguments { line: line_no, found: args.len() });
+                    }
+                    lines.push((line_no, op, [args[0], args[1], args[2]]));
+                }
+            }
+        }
+        let mut instructions = Vec::new();
+        for (line, op, [a, b, c]) in lines {
+            let instruction = Instruction::new(
+                op,
+                symbols.resolve(a, line)?,
+                symbols.resolve(b, line)?,
+                symbols.resolve(c, line)?,
+            );
+            for register in instruction.registers() {
+                check(register, line)?;
+            }
+            instructions.push(instruction);
+        }
+        Ok(Program { ip: symbols.ip, instructions })
+    }
+
+    /// The value an instruction computes, if it only reads the ip register.
+    fn constant(instruction: &Instruction, at: usize, ip: Option<usize>) -> Option<usize> {
+        let mut registers = Vec::new();
+        for (&is_reg, &arg) in instruction.op.register_args().iter().zip(&instruction.args) {
+            if is_reg {
+                if Some(arg) != ip {
+                    return None;
+                }
+                registers.resize(registers.len().max(arg + 1), 0);
+                registers[arg] = at;
+            }
+        }
+        let [a, b, _] = instruction.args;
+        Some(instruction.op.eval(a, b, &registers))
+    }
+
+    fn expression(instruction: &Instruction, at: usize, ip: Option<usize>) -> String {
+        if let Some(value) = constant(instruction, at, ip) {
+            return value.to_string();
+        }
+        let arg = |k: usize| {
+            let value = instruction.args[k];
+            match instruction.op.register_args()[k] {
+                true if Some(value) == ip => at.to_string(),
+                true => format!("r{}", value),
+                false => value.to_string(),
+            }
+        };
+        match instruction.op.symbol() {
+            Some(symbol) => format!("{} {} {}", arg(0), symbol, arg(1)),
+            None => arg(0),
+        }
+    }
+
+    /// Where control goes after an instruction.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Flow {
+        Next,
+        /// Continues at the given address, which may be past the end.
+        Goto(usize),
+        /// Skips the next instruction if the comparison before it held.
+        Branch,
+        /// Continues at an address computed from registers at run time.
+        Computed,
+    }
+
+    impl Flow {
+        /// The addresses control may continue at, or None if computed.
+        pub fn targets(self, at: usize) -> Option<Vec<usize>> {
+            match self {
+                Flow::Next => Some(vec![at + 1]),
+                Flow::Goto(target) => Some(vec![target]),
+                Flow::Branch => Some(vec![at + 1, at + 2]),
+                Flow::Computed => None,
+            }
+        }
+    }
+
+    /// The register the ip register is offset by, for `addr` jumps.
+    fn offset(instruction: &Instruction, ip: usize) -> Option<usize> {
+        match (instruction.op, instruction.args) {
+            (Addr, [a, b, _]) if a == ip && b != ip => Some(b),
+            (Addr, [a, b, _]) if b == ip && a != ip => Some(a),
+            _ => None,
+        }
+    }
+
+    fn is_comparison(op: Opcode) -> bool {
+        matches!(op, Gtir | Gtri | Gtrr | Eqir | Eqri | Eqrr)
+    }
+
+    pub fn flow(program: &Program, at: usize) -> Flow {
+        let instruction = &program.instructions[at];
+        let ip = match program.ip {
+            Some(ip) if instruction.args[2] == ip => ip,
+            _ => return Flow::Next,
+        };
+        if let Some(value) = constant(instruction, at, Some(ip)) {
+            return Flow::Goto(value + 1);
+        }
+        let branch = offset(instruction, ip).zip(at.checked_sub(1)).is_some_and(|(reg, prev)| {
+            let prev = &program.instructions[prev];
+            prev.args[2] == reg && is_comparison(prev.op)
+        });
+        if branch { Flow::Branch } else { Flow::Computed }
+    }
+
+    fn jump(program: &Program, at: usize, ip: usize) -> String {
+        let instruction = &program.instructions[at];
+        let goto = |target: usize| match target < program.instructions.len() {
+            true => format!("goto {}", target),
+            false => "halt".to_string(),
+        };
+        match flow(program, at) {
+            Flow::Next => unreachable!("Not a jump"),
+            Flow::Goto(target) => goto(target),
+            Flow::Branch => {
+                // The comparison's operands are only still current if it did
+                // not overwrite one of them with its result.
+                let comparison = &program.instructions[at - 1];
+                let flag = comparison.args[2];
+                let condition = match comparison.registers().filter(|&r| r == flag).count() {
+                    1 => expression(comparison, at - 1, Some(ip)),
+                    _ => format!("r{}", flag),
+                };
+                format!("if {} {}", condition, goto(at + 2))
+            }
+            Flow::Computed => match offset(instruction, ip) {
+                Some(reg) => format!("goto {} + r{}", at + 1, reg),
+                None => format!("goto ({}) + 1", expression(instruction, at, Some(ip))),
+            },
+        }
+    }
+
+    /// Renders a program as numbered pseudo-code. Reads of the ip register
+    /// become the current address, writes to it become gotos, and a
+    /// comparison followed by a jump over its result becomes an `if`.
+    pub fn disassemble(program: &Program) -> String {
+        let mut text = String::new();
+        if let Some(ip) = program.ip {
+            text += &format!("#ip {}\n", ip);
+        }
+        for (at, instruction) in program.instructions.iter().enumerate() {
+            let line = match program.ip {
+                Some(ip) if instruction.args[2] == ip => jump(program, at, ip),
+                ip => format!("r{} = {}", instruction.args[2], expression(instruction, at, ip)),
+            };
+            text += &format!("{:>3}: {}\n", at, line);
+        }
+        text
+    }
+
+    impl fmt::Display for Program {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            if let Some(ip) = self.ip {
+                writeln!(f, "#ip {}", ip)?;
+            }
+            for instruction in &self.instructions {
+                writeln!(f, "{}", instruction)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Block {
+        pub start: usize,
+        pub end: usize,
+        /// Indices of the blocks control can continue in.
+        pub successors: Vec<usize>,
+        /// Control can leave the program from this block.
+        pub exits: bool,
+        /// The block ends in a jump computed at run time.
+        pub computed: bool,
+    }
+
+    /// A natural loop: the header dominates every block in the body, and the
+    /// latches jump back to it.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Loop {
+        pub header: usize,
+        pub latches: Vec<usize>,
+        pub body: BTreeSet<usize>,
+    }
+
+    /// The control-flow graph of a program, split into basic blocks.
+    #[derive(Clone, Debug)]
+    pub struct Cfg {
+        pub blocks: Vec<Block>,
+        pub loops: Vec<Loop>,
+        program: Program,
+    }
+
+    impl Cfg {
+        pub fn new(program: &Program) -> Cfg {
+            let len = program.instructions.len();
+            let flows: Vec<Flow> = (0..len).map(|at| flow(program, at)).collect();
+            let mut leaders = BTreeSet::new();
+            leaders.insert(0);
+            for (at, &flow) in flows.iter().enumerate().filter(|(_, &f)| f != Flow::Next) {
+                leaders.insert(at + 1);
+                leaders.extend(flow.targets(at).unwrap_or_default());
+            }
+            let starts: Vec<usize> = leaders.into_iter().filter(|&at| at < len).collect();
+            let block_of = |at: usize| starts.partition_point(|&start| start <= at) - 1;
+            let mut blocks = Vec::new();
+            for (i, &start) in starts.iter().enumerate() {
+                let end = starts.get(i + 1).copied().unwrap_or(len);
+                let targets = flows[end - 1].targets(end - 1);
+                let mut successors: Vec<usize> = targets.iter().flatten()
+                    .filter(|&&target| target < len)
+                    .map(|&target| block_of(target))
+                    .collect();
+                successors.dedup();
+                blocks.push(Block {
+                    start,
+                    end,
+                    successors,
+                    exits: targets.iter().flatten().any(|&target| target >= len),
+                    computed: targets.is_none(),
+                });
+            }
+            let loops = natural_loops(&blocks);
+            Cfg { blocks, loops, program: program.clone() }
+        }
+
+        pub fn block_of(&self, at: usize) -> Option<usize> {
+            self.blocks.iter().position(|block| (block.start..block.end).contains(&at))
+        }
+
+        /// How many loops contain the block.
+        pub fn depth(&self, block: usize) -> usize {
+            self.loops.iter().filter(|l| l.body.contains(&block)).count()
+        }
+
+        /// Renders the graph in Graphviz DOT, with loop headers doubled and
+        /// back edges in red.
+        pub fn to_dot(&self) -> String {
+            let listing = disassemble(&self.program);
+            let lines: Vec<&str> = listing.lines().filter(|l| !l.starts_with('#')).collect();
+            let mut dot = String::from("digraph elfcode {\n    node [shape=box, fontname=monospace];\n");
+            for (i, block) in self.blocks.iter().enumerate() {
+                let label: String = lines[block.start..block.end].iter()
+                    .map(|line| format!("{}\\l", line.trim_start()))
+                    .collect();
+                let header = if self.loops.iter().any(|l| l.header == i) { ", peripheries=2" } else { "" };
+                dot += &format!("    b{} [label=\"{}\"{}];\n", i, label, header);
+            }
+            for (i, block) in self.blocks.iter().enumerate() {
+                for &next in &block.successors {
+                    let back = self.loops.iter().any(|l| l.header == next && l.latches.contains(&i));
+                    let style = if back { " [color=red]" } else { "" };
+                    dot += &format!("    b{} -> b{}{};\n", i, next, style);
+                }
+                if block.exits {
+                    dot += &format!("    b{} -> halt;\n", i);
+                }
+                if block.computed {
+                    dot += &format!("    b{} -> computed [style=dashed];\n", i);
+                }
+            }
+            dot += "}\n";
+            dot
+        }
+    }
+
+    fn natural_loops(blocks: &[Block]) -> Vec<Loop> {
+        if blocks.is_empty() {
+            return Vec::new();
+        }
+        let all: BTreeSet<usize> = (0..blocks.len()).collect();
+        let mut predecessors = vec![Vec::new(); blocks.len()];
+        for (i, block) in blocks.iter().enumerate() {
+            for &next in &block.successors {
+                predecessors[next].push(i);
+            }
+        }
+        let mut dominators = vec![all.clone(); blocks.len()];
+        dominators[0] = BTreeSet::from([0]);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 1..blocks.len() {
+                let mut doms = predecessors[i].iter()
+                    .map(|&p| dominators[p].clone())
+                    .reduce(|a, b| &a & &b)
+                    .unwrap_or_default();
+                doms.insert(i);
+                if doms != dominators[i] {
+                    dominators[i] = doms;
+                    changed = true;
+                }
+            }
+        }
+        let mut loops: Vec<Loop> = Vec::new();
+        for (latch, block) in blocks.iter().enumerate() {
+            for &header in block.successors.iter().filter(|&&h| dominators[latch].contains(&h)) {
+                let mut body = BTreeSet::from([header, latch]);
+                let mut stack = vec![latch];
+                while let Some(node) = stack.pop() {
+                    if node != header {
+                        for &p in &predecessors[node] {
+                            if body.insert(p) {
+                                stack.push(p);
+                            }
+                        }
+                    }
+                }
+                match loops.iter_mut().find(|l| l.header == header) {
+                    Some(existing) => {
+                        existing.latches.push(latch);
+                        existing.body.extend(body);
+                    }
+                    None => loops.push(Loop { header, latches: vec![latch], body }),
+                }
+            }
+        }
+        loops.sort_by_key(|l| l.header);
+        loops
+    }
+
+    /// A loop the optimizer replaces with a single step. Fields name registers
+    /// except `divisor`, and `flag` is the register the loop compares into.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Superinstruction {
+        /// Adds `x` to `acc` once per increment of `counter` up to `bound`.
+        MultiplyAdd { acc: usize, x: usize, counter: usize, bound: usize, flag: usize },
+        /// Adds `i` to `acc` if some `j` up to `n` has `i * j == n`.
+        DivisorTest { acc: usize, i: usize, j: usize, n: usize, flag: usize },
+        /// Adds the sum of the divisors of `n` to `acc`, trying every `i` and `j`.
+        DivisorSum { acc: usize, i: usize, j: usize, n: usize, flag: usize },
+        /// Counts `quotient` up until `(quotient + 1) * divisor > n`.
+        Divide { quotient: usize, divisor: usize, n: usize, flag: usize },
+    }
+
+    fn divisor_sum(n: usize) -> usize {
+        (1..).take_while(|&d| d <= n / d)
+            .filter(|&d| n.is_multiple_of(d))
+            .map(|d| if d * d == n { d } else { d + n / d })
+            .sum()
+    }
+
+    impl Superinstruction {
+        /// Leaves the registers as the loop would on exit. Returns false,
+        /// changing nothing, if the loop would never exit.
+        fn execute(self, r: &mut [usize]) -> bool {
+            match self {
+                Superinstruction::MultiplyAdd { acc, x, counter, bound, flag } => {
+                    let last = r[counter].max(r[bound]);
+                    if last == usize::MAX {
+                        return false;
+                    }
+                    r[acc] = r[acc].wrapping_add(r[x].wrapping_mul(last - r[counter] + 1));
+                    r[counter] = last + 1;
+                    r[flag] = 1;
+                }
+                Superinstruction::DivisorTest { acc, i, j, n, flag } => {
+                    let last = r[j].max(r[n]);
+                    if last == usize::MAX {
+                        return false;
+                    }
+                    if r[i] > 0 && r[n].is_multiple_of(r[i]) && (r[j]..=last).contains(&(r[n] / r[i])) {
+                        r[acc] = r[acc].wrapping_add(r[i]);
+                    }
+                    r[j] = last + 1;
+                    r[flag] = 1;
+                }
+                Superinstruction::DivisorSum { acc, i, j, n, flag } => {
+                    if r[n] == usize::MAX {
+                        return false;
+                    }
+                    r[acc] = r[acc].wrapping_add(divisor_sum(r[n]));
+                    r[i] = r[n].max(1) + 1;
+                    r[j] = r[n].max(1) + 1;
+                    r[flag] = 1;
+                }
+                Superinstruction::Divide { quotient, divisor, n, flag } => {
+                    if divisor == 0 {
+                        return false;
+                    }
+                    r[quotient] = r[quotient].max(r[n] / divisor);
+                    r[flag] = 1;
+                }
+            }
+            true
+        }
+    }
+
+    /// A superinstruction replacing the loop from `start` up to `exit`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Idiom {
+        pub start: usize,
+        pub exit: usize,
+        pub op: Superinstruction,
+    }
+
+    type Bindings = HashMap<char, usize>;
+    type Build = fn(&Bindings) -> Superinstruction;
+
+    // Idioms in the assembler's syntax. Upper case letters match distinct
+    // registers other than ip, lower case letters match values, `_` matches
+    // anything, and `@n` is a jump to template line `n`.
+    const IDIOMS: [(&str, Build); 4] = [
+        ("seti 1 _ I
+          seti 1 _ J
+          mulr I J T
+          eqrr T N T
+          addr T ip ip
+          addi ip 1 ip
+          addr I A A
+          addi J 1 J
+          gtrr J N T
+          addr T ip ip
+          seti @2 _ ip
+          addi I 1 I
+          gtrr I N T
+          addr T ip ip
+          seti @1 _ ip",
+         |b| Superinstruction::DivisorSum { acc: b[&'A'], i: b[&'I'], j: b[&'J'], n: b[&'N'], flag: b[&'T'] }),
+        ("mulr I J T
+          eqrr T N T
+          addr T ip ip
+          addi ip 1 ip
+          addr I A A
+          addi J 1 J
+          gtrr J N T
+          addr T ip ip
+          seti @0 _ ip",
+         |b| Superinstruction::DivisorTest { acc: b[&'A'], i: b[&'I'], j: b[&'J'], n: b[&'N'], flag: b[&'T'] }),
+        ("addr X A A
+          addi C 1 C
+          gtrr C N T
+          addr T ip ip
+          seti @0 _ ip",
+         |b| Superinstruction::MultiplyAdd { acc: b[&'A'], x: b[&'X'], counter: b[&'C'], bound: b[&'N'], flag: b[&'T'] }),
+        ("addi Q 1 T
+          muli T k T
+          gtrr T N T
+          addr T ip ip
+          addi ip 1 ip
+          seti @8 _ ip
+          addi Q 1 Q
+          seti @0 _ ip",
+         |b| Superinstruction::Divide { quotient: b[&'Q'], divisor: b[&'k'], n: b[&'N'], flag: b[&'T'] }),
+    ];
+
+    /// The optimizer's idiom templates, for generating programs that use them.
+    pub fn idiom_templates() -> impl Iterator<Item=&'static str> {
+        IDIOMS.iter().map(|&(template, _)| template)
+    }
+
+    fn bind(token: &str, value: usize, start: usize, ip: usize, bindings: &mut Bindings) -> bool {
+        let var = token.chars().next().unwrap();
+        match token {
+            "_" => true,
+            "ip" => value == ip,
+            _ if token.starts_with('@') => token[1..].parse::<usize>().is_ok_and(|line| start + line == value + 1),
+            _ if var.is_ascii_digit() => token.parse() == Ok(value),
+            _ => match bindings.get(&var) {
+                Some(&bound) => bound == value,
+                None if var.is_ascii_uppercase() => {
+                    let taken = bindings.iter().any(|(k, &v)| k.is_ascii_uppercase() && v == value);
+                    if value == ip || taken {
+                        return false;
+                    }
+                    bindings.insert(var, value);
+                    true
+                }
+                None => {
+                    bindings.insert(var, value);
+                    true
+                }
+            },
+        }
+    }
+
+    fn unify(template: &[Vec<&str>], code: &[Instruction], start: usize, ip: usize, bindings: Bindings)
+        -> Option<Bindings> {
+        let (pattern, instruction) = match (template.first(), code.first()) {
+            (None, _) => return Some(bindings),
+            (Some(pattern), Some(instruction)) if pattern[0] == instruction.op.name() => (pattern, instruction),
+            _ => return None,
+        };
+        let [a, b, c] = instruction.args;
+        let mut orders = vec![[a, b]];
+        if matches!(instruction.op, Addr | Mulr | Banr | Borr | Eqrr) && a != b {
+            orders.push([b, a]);
+        }
+        orders.into_iter().find_map(|[a, b]| {
+            let mut bindings = bindings.clone();
+            let bound = [a, b, c].iter().zip(&pattern[1..])
+                .all(|(&value, token)| bind(token, value, start, ip, &mut bindings));
+            if bound {
+                unify(&template[1..], &code[1..], start, ip, bindings)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Finds the loops that can run as superinstructions.
+    pub fn find_idioms(program: &Program) -> Vec<Idiom> {
+        let ip = match program.ip {
+            Some(ip) => ip,
+            None => return Vec::new(),
+        };
+        let templates: Vec<(Vec<Vec<&str>>, _)> = IDIOMS.iter()
+            .map(|(text, build)| (text.lines().map(|l| l.split_whitespace().collect()).collect(), build))
+            .collect();
+        let mut idioms = Vec::new();
+        for start in 0..program.instructions.len() {
+            for (template, build) in &templates {
+                let code = &program.instructions[start..];
+                if let Some(bindings) = unify(template, code, start, ip, HashMap::new()) {
+                    idioms.push(Idiom { start, exit: start + template.len(), op: build(&bindings) });
+                    break;
+                }
+            }
+        }
+        idioms
+    }
+
+    /// Runs a program. When the program binds the instruction pointer to a
+    /// register, that register is loaded with `ip` before each instruction
+    /// and read back afterwards.
+    #[derive(Clone, Debug)]
+    pub struct Vm {
+        pub registers: Vec<usize>,
+        pub ip: usize,
+        pub steps: u64,
+        /// How many times each instruction has been executed.
+        pub hits: Vec<u64>,
+        pub arithmetic: Arithmetic,
+        ip_register: Option<usize>,
+        instructions: Vec<Instruction>,
+        idioms: HashMap<usize, Idiom>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum VmError {
+        Overflow { at: usize, instruction: Instruction, registers: Vec<usize> },
+        Register { at: usize, register: usize, count: usize },
+    }
+
+    impl fmt::Display for VmError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                VmError::Overflow { at, instruction, registers } =>
+                    write!(f, "Overflow at {} ({}) with registers {:?}", at, instruction, registers),
+                VmError::Register { at, register, count } =>
+                    write!(f, "Register {} at {} is out of range for {} registers", register, at, count),
+            }
+        }
+    }
+
+    impl Vm {
+        pub fn new(program: &Program, registers: usize) -> Vm {
+            Vm {
+                registers: vec![0; registers],
+                ip: 0,
+                steps: 0,
+                hits: vec![0; program.instructions.len()],
+                arithmetic: Arithmetic::default(),
+                ip_register: program.ip,
+                instructions: program.instructions.clone(),
+                idioms: HashMap::new(),
+            }
+        }
+
+        /// Runs recognised loops as superinstructions from now on, each
+        /// counting as a single step. Returns how many were found.
+        pub fn optimize(&mut self) -> usize {
+            let count = self.registers.len();
+            let instructions = &self.instructions;
+            let fits = |idiom: &Idiom| instructions[idiom.start..idiom.exit].iter()
+                .flat_map(Instruction::registers)
+                .all(|r| r < count);
+            let idioms = find_idioms(&self.program());
+            self.idioms = idioms.into_iter().filter(fits).map(|idiom| (idiom.start, idiom)).collect();
+            self.idioms.len()
+        }
+
+        /// Superinstructions skip the overflows of the loops they replace, so
+        /// they only run with wrapping arithmetic.
+        fn accelerate(&mut self) -> bool {
+            if self.arithmetic != Arithmetic::Wrapping {
+                return false;
+            }
+            let idiom = match self.idioms.get(&self.ip) {
+                Some(&idiom) => idiom,
+                None => return false,
+            };
+            if !idiom.op.execute(&mut self.registers) {
+                return false;
+            }
+            self.hits[idiom.start] += 1;
+            self.steps += 1;
+            self.ip = idiom.exit;
+            if let Some(reg) = self.ip_register {
+                self.registers[reg] = idiom.exit - 1;
+            }
+            true
+        }
+
+        pub fn instructions(&self) -> &[Instruction] {
+            &self.instructions
+        }
+
+        pub fn current(&self) -> Option<&Instruction> {
+            self.instructions.get(self.ip)
+        }
+
+        pub fn halted(&self) -> bool {
+            self.current().is_none()
+        }
+
+        /// Executes one instruction, returning false if the program has halted.
+        /// Panics on the errors `try_step` returns.
+        pub fn step(&mut self) -> bool {
+            self.try_step().unwrap_or_else(|err| panic!("{}", err))
+        }
+
+        /// Executes one instruction, returning false if the program has halted.
+        /// On error nothing is changed.
+        pub fn try_step(&mut self) -> Result<bool, VmError> {
+            let instruction = match self.current() {
+                Some(&instruction) => instruction,
+                None => return Ok(false),
+            };
+            let count = self.registers.len();
+            let out_of_range = self.ip_register.into_iter().chain(instruction.registers()).find(|&r| r >= count);
+            if let Some(register) = out_of_range {
+                return Err(VmError::Register { at: self.ip, register, count });
+            }
+            if self.accelerate() {
+                return Ok(true);
+            }
+            let saved = self.ip_register.map(|reg| (reg, self.registers[reg]));
+            if let Some((reg, _)) = saved {
+                self.registers[reg] = self.ip;
+            }
+            let [a, b, c] = instruction.args;
+            match self.arithmetic.eval(instruction.op, a, b, &self.registers) {
+                Some(value) => self.registers[c] = value,
+                None => {
+                    let registers = self.registers.clone();
+                    if let Some((reg, value)) = saved {
+                        self.registers[reg] = value;
+                    }
+                    return Err(VmError::Overflow { at: self.ip, instruction, registers });
+                }
+            }
+            self.hits[self.ip] += 1;
+            self.steps += 1;
+            if let Some(reg) = self.ip_register {
+                self.ip = self.registers[reg];
+            }
+            self.ip += 1;
+            Ok(true)
+        }
+
+        pub fn run(&mut self) {
+            while self.step() {}
+        }
+
+        pub fn try_run(&mut self) -> Result<(), VmError> {
+            while self.try_step()? {}
+            Ok(())
+        }
+
+        /// Runs until `stop` holds before an instruction, returning false if
+        /// the program halted first.
+        pub fn run_until<F: FnMut(&Vm) -> bool>(&mut self, mut stop: F) -> bool {
+            loop {
+                if self.halted() {
+                    return false;
+                }
+                if stop(self) {
+                    return true;
+                }
+                self.step();
+            }
+        }
+
+        pub fn program(&self) -> Program {
+            Program { ip: self.ip_register, instructions: self.instructions.clone() }
+        }
+    }
+
+    /// An instruction observed with the registers before and after it ran,
+    /// as in the day 16 samples.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Sample<W = usize> {
+        pub before: Vec<W>,
+        pub instruction: [usize; 4],
+        pub after: Vec<W>,
+    }
+
+    impl<W: Word> Sample<W> {
+        /// Whether `op` with this sample's arguments turns `before` into `after`.
+        pub fn fits(&self, op: Opcode) -> bool {
+            let [_, a, b, c] = self.instruction;
+            let len = self.before.len();
+            let [reg_a, reg_b] = op.register_args();
+            if self.after.len() != len || c >= len || (reg_a && a >= len) || (reg_b && b >= len) {
+                return false;
+            }
+            let mut registers = self.before.clone();
+            registers[c] = op.eval(a, b, &registers);
+            registers == self.after
+        }
+
+        pub fn candidates(&self) -> Vec<Opcode> {
+            Opcode::ALL.iter().copied().filter(|&op| self.fits(op)).collect()
+        }
+    }
+
+    pub type Mapping = BTreeMap<usize, Opcode>;
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Inference {
+        Unique(Mapping),
+        Ambiguous(Vec<Mapping>),
+        /// Codes no opcode fits, with the first sample ruling out each opcode.
+        Contradiction(BTreeMap<usize, Vec<(Opcode, usize)>>),
+        /// Every code fits some opcode, but no two codes can share one.
+        Unassignable(BTreeMap<usize, Vec<Opcode>>),
+    }
+
+    impl fmt::Display for Inference {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let names = |ops: &mut dyn Iterator<Item=&Opcode>| {
+                ops.map(|op| op.name()).collect::<Vec<_>>().join(", ")
+            };
+            match self {
+                Inference::Unique(mapping) => {
+                    write!(f, "Unique mapping:")?;
+                    for (code, op) in mapping {
+                        write!(f, " {}={}", code, op)?;
+                    }
+                    Ok(())
+                }
+                Inference::Ambiguous(mappings) => {
+                    write!(f, "{} or more consistent mappings:", mappings.len())?;
+                    for &code in mappings[0].keys() {
+                        let ops: BTreeSet<&Opcode> = mappings.iter().map(|m| &m[&code]).collect();
+                        if ops.len() > 1 {
+                            write!(f, " {} could be {};", code, names(&mut ops.into_iter()))?;
+                        }
+                    }
+                    Ok(())
+                }
+                Inference::Contradiction(codes) => {
+                    for (code, ruled_out) in codes {
+                        write!(f, "Code {} fits no opcode:", code)?;
+                        for (op, sample) in ruled_out {
+                            write!(f, " {} (sample {})", op, sample)?;
+                        }
+                        writeln!(f)?;
+                    }
+                    Ok(())
+                }
+                Inference::Unassignable(codes) => {
+                    write!(f, "Codes cannot all have different opcodes:")?;
+                    for (code, ops) in codes {
+                        write!(f, " {} fits {};", code, names(&mut ops.iter()))?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Whether each of `codes` can still get a different unused opcode.
+    fn matchable(codes: &[(usize, Vec<Opcode>)], used: &BTreeSet<Opcode>) -> bool {
+        fn augment(code: usize, codes: &[(usize, Vec<Opcode>)], owner: &mut HashMap<Opcode, usize>,
+                   visited: &mut BTreeSet<Opcode>) -> bool {
+            for &op in &codes[code].1 {
+                if visited.insert(op) {
+                    let free = match owner.get(&op) {
+                        None => true,
+                        Some(&other) => augment(other, codes, owner, visited),
+                    };
+                    if free {
+                        owner.insert(op, code);
+                        return true;
+                    }
+                }
+            }
+            false
+        }
+        let mut owner = HashMap::new();
+        (0..codes.len()).all(|code| augment(code, codes, &mut owner, &mut used.clone()))
+    }
+
+    fn assign(codes: &[(usize, Vec<Opcode>)], used: &mut BTreeSet<Opcode>, current: &mut Mapping,
+              found: &mut Vec<Mapping>, limit: usize) {
+        let ((code, ops), rest) = match codes.split_first() {
+            Some(first) => first,
+            None => return found.push(current.clone()),
+        };
+        for &op in ops {
+            if found.len() >= limit {
+                return;
+            }
+            if used.insert(op) {
+                if matchable(rest, used) {
+                    current.insert(*code, op);
+                    assign(rest, used, current, found, limit);
+                    current.remove(code);
+                }
+                used.remove(&op);
+            }
+        }
+    }
+
+    /// Works out which opcode each instruction number in the samples means,
+    /// listing at most `limit` mappings (but at least two) when ambiguous.
+    pub fn infer<W: Word>(samples: &[Sample<W>], limit: usize) -> Inference {
+        let mut ruled_out: BTreeMap<usize, BTreeMap<Opcode, usize>> = BTreeMap::new();
+        for (index, sample) in samples.iter().enumerate() {
+            let entry = ruled_out.entry(sample.instruction[0]).or_default();
+            for op in Opcode::ALL.iter().filter(|&&op| !sample.fits(op)) {
+                entry.entry(*op).or_insert(index);
+            }
+        }
+        let contradictions: BTreeMap<usize, Vec<(Opcode, usize)>> = ruled_out.iter()
+            .filter(|(_, out)| out.len() == Opcode::ALL.len())
+            .map(|(&code, out)| (code, out.iter().map(|(&op, &sample)| (op, sample)).collect()))
+            .collect();
+        if !contradictions.is_empty() {
+            return Inference::Contradiction(contradictions);
+        }
+        let mut codes: Vec<(usize, Vec<Opcode>)> = ruled_out.iter()
+            .map(|(&code, out)| (code, Opcode::ALL.iter().copied().filter(|op| !out.contains_key(op)).collect()))
+            .collect();
+        codes.sort_by_key(|(code, ops)| (ops.len(), *code));
+        let mut found = Vec::new();
+        assign(&codes, &mut BTreeSet::new(), &mut Mapping::new(), &mut found, limit.max(2));
+        match found.len() {
+            0 => Inference::Unassignable(codes.into_iter().collect()),
+            1 => Inference::Unique(found.pop().unwrap()),
+            _ => Inference::Ambiguous(found),
+        }
+    }
+
+    /// Writes an instruction's result, or returns false if it overflows.
+    type Step = Box<dyn Fn(&mut [usize]) -> bool + Send + Sync>;
+
+    /// A closure evaluating `instruction` through `Opcode::eval` or, unless
+    /// wrapping, `Arithmetic::eval`. Each arm names its opcode, so the
+    /// evaluation is specialised rather than dispatched on every step.
+    fn compile(instruction: &Instruction, arithmetic: Arithmetic) -> Step {
+        let [a, b, c] = instruction.args;
+        macro_rules! specialise {
+            ($($op:ident)*) => {
+                match (instruction.op, arithmetic) {
+                    $(($op, Arithmetic::Wrapping) => Box::new(move |r: &mut [usize]| {
+                        r[c] = $op.eval(a, b, r);
+                        true
+                    }),)*
+                    $(($op, _) => Box::new(move |r: &mut [usize]| match arithmetic.eval($op, a, b, r) {
+                        Some(value) => {
+                            r[c] = value;
+                            true
+                        }
+                        None => false,
+                    }),)*
+                }
+            };
+        }
+        specialise!(Addr Addi Mulr Muli Banr Bani Borr Bori Setr Seti Gtir Gtri Gtrr Eqir Eqri Eqrr)
+    }
+
+    /// A program resolved ahead of time into one closure per instruction,
+    /// with opcode and operands baked in, that runs on registers in place.
+    /// It has none of the `Vm`'s counters, idioms or debugging hooks.
+    pub struct Compiled {
+        ip_register: Option<usize>,
+        instructions: Vec<Instruction>,
+        code: Vec<Step>,
+    }
+
+    impl Compiled {
+        pub fn new(program: &Program) -> Compiled {
+            Compiled::with_arithmetic(program, Arithmetic::Wrapping)
+        }
+
+        pub fn with_arithmetic(program: &Program, arithmetic: Arithmetic) -> Compiled {
+            Compiled {
+                ip_register: program.ip,
+                instructions: program.instructions.clone(),
+                code: program.instructions.iter().map(|i| compile(i, arithmetic)).collect(),
+            }
+        }
+
+        /// Runs from `ip` until the program halts or `stop` holds before an
+        /// instruction. Returns the ip it stopped at and the steps taken, or
+        /// the overflow that stopped it, with registers as `Vm` reports them.
+        pub fn try_run_until<F>(&self, registers: &mut [usize], mut ip: usize, mut stop: F)
+            -> Result<(usize, u64), VmError>
+            where F: FnMut(usize, &[usize]) -> bool {
+            let mut steps = 0;
+            while let Some(step) = self.code.get(ip) {
+                if stop(ip, registers) {
+                    break;
+                }
+                let saved = self.ip_register.map(|reg| (reg, registers[reg]));
+                if let Some((reg, _)) = saved {
+                    registers[reg] = ip;
+                }
+                if !step(registers) {
+                    let error = VmError::Overflow {
+                        at: ip, instruction: self.instructions[ip], registers: registers.to_vec(),
+                    };
+                    if let Some((reg, value)) = saved {
+                        registers[reg] = value;
+                    }
+                    return Err(error);
+                }
+                ip = match self.ip_register {
+                    Some(reg) => registers[reg] + 1,
+                    None => ip + 1,
+                };
+                steps += 1;
+            }
+            Ok((ip, steps))
+        }
+
+        /// `try_run_until`, panicking on overflow.
+        pub fn run_until<F>(&self, registers: &mut [usize], ip: usize, stop: F) -> (usize, u64)
+            where F: FnMut(usize, &[usize]) -> bool {
+            self.try_run_until(registers, ip, stop).unwrap_or_else(|err| panic!("{}", err))
+        }
+
+        /// Runs from the start until the program halts, returning the steps taken.
+        pub fn run(&self, registers: &mut [usize]) -> u64 {
+            self.run_until(registers, 0, |_, _| false).1
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum Event {
+        Stepped,
+        Breakpoint(usize),
+        Watchpoint { at: usize, register: usize, old: usize, new: usize },
+        Fault(VmError),
+        Halted,
+    }
+
+    impl fmt::Display for Event {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Event::Stepped => write!(f, "Stepped"),
+                Event::Breakpoint(at) => write!(f, "Breakpoint at {}", at),
+                Event::Watchpoint { at, register, old, new } =>
+                    write!(f, "r{} changed from {} to {} at {}", register, old, new, at),
+                Event::Fault(err) => write!(f, "{}", err),
+                Event::Halted => write!(f, "Halted"),
+            }
+        }
+    }
+
+    /// Runs a `Vm` with breakpoints before instructions, watchpoints on
+    /// register changes, and an optional trace of every executed instruction.
+    pub struct Debugger {
+        pub vm: Vm,
+        breakpoints: BTreeSet<usize>,
+        watchpoints: BTreeSet<usize>,
+        trace: Option<Box<dyn Write>>,
+    }
+
+    impl Debugger {
+        pub fn new(vm: Vm) -> Debugger {
+            Debugger { vm, breakpoints: BTreeSet::new(), watchpoints: BTreeSet::new(), trace: None }
+        }
+
+        pub fn set_breakpoint(&mut self, at: usize) {
+            self.breakpoints.insert(at);
+        }
+
+        pub fn clear_breakpoint(&mut self, at: usize) -> bool {
+            self.breakpoints.remove(&at)
+        }
+
+        pub fn watch(&mut self, register: usize) -> Result<(), String> {
+            if register >= self.vm.registers.len() {
+                return Err(format!("Invalid register \"r{}\"", register));
+            }
+            self.watchpoints.insert(register);
+            Ok(())
+        }
+
+        pub fn unwatch(&mut self, register: usize) -> bool {
+            self.watchpoints.remove(&register)
+        }
+
+        pub fn trace_to(&mut self, out: Box<dyn Write>) {
+            self.trace = Some(out);
+        }
+
+        pub fn step(&mut self) -> io::Result<Event> {
+            let at = self.vm.ip;
+            let instruction = match self.vm.current() {
+                Some(&instruction) => instruction,
+                None => return Ok(Event::Halted),
+            };
+            let before = self.vm.registers.clone();
+            if let Err(err) = self.vm.try_step() {
+                return Ok(Event::Fault(err));
+            }
+            if let Some(trace) = &mut self.trace {
+                writeln!(trace, "{:>8} {:>3}: {:<16} {:?}",
+                         self.vm.steps, at, instruction.to_string(), self.vm.registers)?;
+            }
+            for &register in &self.watchpoints {
+                let (old, new) = (before[register], self.vm.registers[register]);
+                if old != new {
+                    return Ok(Event::Watchpoint { at, register, old, new });
+                }
+            }
+            Ok(Event::Stepped)
+        }
+
+        /// Steps until a breakpoint, watchpoint or the end of the program.
+        pub fn resume(&mut self) -> io::Result<Event> {
+            loop {
+                match self.step()? {
+                    Event::Stepped => (),
+                    event => return Ok(event),
+                }
+                if self.breakpoints.contains(&self.vm.ip) {
+                    return Ok(Event::Breakpoint(self.vm.ip));
+                }
+            }
+        }
+
+        pub fn dump(&self) -> String {
+            let next = self.vm.current().map_or("halted".to_string(), |i| i.to_string());
+            format!("ip={} steps={} next: {} registers: {:?}",
+                    self.vm.ip, self.vm.steps, next, self.vm.registers)
+        }
+
+        fn register(&self, arg: Option<&str>) -> Result<usize, String> {
+            let arg = arg.ok_or("Missing register")?;
+            match arg.trim_start_matches('r').parse::<usize>() {
+                Ok(r) if r < self.vm.registers.len() => Ok(r),
+                _ => Err(format!("Invalid register {:?}", arg)),
+            }
+        }
+
+        fn command(&mut self, line: &str, output: &mut dyn Write) -> io::Result<bool> {
+            let mut words = line.split_whitespace();
+            let number = |arg: Option<&str>| arg.and_then(|a| a.parse::<usize>().ok());
+            let result = match (words.next(), words.next()) {
+                (None, _) => Ok(()),
+                (Some("q" | "quit"), _) => return Ok(false),
+                (Some("s" | "step"), count) => {
+                    for _ in 0..number(count).unwrap_or(1) {
+                        let event = self.step()?;
+                        if event != Event::Stepped {
+                            writeln!(output, "{}", event)?;
+                            break;
+                        }
+                    }
+                    writeln!(output, "{}", self.dump())?;
+                    Ok(())
+                }
+                (Some("c" | "continue"), _) => {
+                    let event = self.resume()?;
+                    writeln!(output, "{}\n{}", event, self.dump())?;
+                    Ok(())
+                }
+                (Some("b" | "break"), at) => number(at)
+                    .map(|at| self.set_breakpoint(at))
+                    .ok_or_else(|| "Expected an instruction index".to_string()),
+                (Some("d" | "delete"), at) => number(at)
+                    .filter(|&at| self.clear_breakpoint(at))
+                    .map(|_| ())
+                    .ok_or_else(|| "No such breakpoint".to_string()),
+                (Some("w" | "watch"), reg) => self.register(reg).and_then(|r| self.watch(r)),
+                (Some("u" | "unwatch"), reg) => self.register(reg)
+                    .and_then(|r| if self.unwatch(r) { Ok(()) } else { Err("Not watched".to_string()) }),
+                (Some("set"), reg) => self.register(reg).and_then(|r| {
+                    self.vm.registers[r] = number(words.next()).ok_or("Expected a value")?;
+                    Ok(())
+                }),
+                (Some("r" | "regs"), _) => writeln!(output, "{}", self.dump()).map_err(|e| e.to_string()),
+                (Some("l" | "list"), _) => {
+                    let listing = disassemble(&self.vm.program());
+                    let lines = listing.lines().filter(|l| !l.starts_with('#'));
+                    let start = self.vm.ip.saturating_sub(5);
+                    for (at, line) in lines.enumerate().skip(start).take(11) {
+                        let marker = if at == self.vm.ip { '>' } else { ' ' };
+                        writeln!(output, "{}{}", marker, line)?;
+                    }
+                    Ok(())
+                }
+                (Some(other), _) => Err(format!("Unknown command {:?}", other)),
+            };
+            if let Err(message) = result {
+                writeln!(output, "{}", message)?;
+            }
+            Ok(true)
+        }
+
+        /// Reads commands until `quit` or the end of the input:
+        /// `step [n]`, `continue`, `break <at>`, `delete <at>`, `watch <rN>`,
+        /// `unwatch <rN>`, `set <rN> <value>`, `regs` and `list`.
+        pub fn repl(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+            write!(output, "(elf) ")?;
+            output.flush()?;
+            for line in input.lines() {
+                if !self.command(&line?, &mut output)? {
+                    break;
+                }
+                write!(output, "(elf) ")?;
+                output.flush()?;
+            }
+            writeln!(output)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn runs_sample_program() {
+            let program: Program = "#ip 0\nseti 5 0 1\nseti 6 0 2\naddi 0 1 0\naddr 1 2 3\n\
+                setr 1 0 0\nseti 8 0 4\nseti 9 0 5".parse().unwrap();
+            let mut vm = Vm::new(&program, 6);
+            assert!(vm.run_until(|vm| vm.ip == 4));
+            assert_eq!(vm.registers, [3, 5, 6, 0, 0, 0]);
+            vm.run();
+            assert_eq!(vm.registers, [6, 5, 6, 0, 0, 9]);
+            assert_eq!(vm.steps, 5);
+            assert_eq!(vm.hits, [1, 1, 1, 0, 1, 0, 1]);
+            assert_eq!(program.to_string().parse(), Ok(program));
+            assert_eq!("#ip 0\nmuli 1 2\n".parse::<Program>(),
+                       Err(ParseProgramError::Arguments { line: 2, found: 2 }));
+
+            let matching = Opcode::ALL.iter()
+                .filter(|op| op.eval(2, 1, &[3usize, 2, 1, 1]) == 2)
+                .count();
+            assert_eq!(matching, 3);
+        }
+
+        #[test]
+        fn debugger_stops_and_traces() {
+            let program: Program = "#ip 0\nseti 5 0 1\nseti 6 0 2\naddi 0 1 0\naddr 1 2 3\n\
+                setr 1 0 0\nseti 8 0 4\nseti 9 0 5".parse().unwrap();
+            let path = std::env::temp_dir().join(format!("elf-trace-{}", std::process::id()));
+            let mut debugger = Debugger::new(Vm::new(&program, 6));
+            debugger.trace_to(Box::new(std::fs::File::create(&path).unwrap()));
+            debugger.set_breakpoint(4);
+            debugger.watch(5).unwrap();
+            assert_eq!(debugger.watch(6), Err("Invalid register \"r6\"".to_string()));
+            assert_eq!(debugger.resume().unwrap(), Event::Breakpoint(4));
+            assert_eq!(debugger.resume().unwrap(), Event::Watchpoint { at: 6, register: 5, old: 0, new: 9 });
+            assert_eq!(debugger.resume().unwrap(), Event::Halted);
+            drop(debugger);
+            let trace = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(trace.lines().count(), 5);
+            assert!(trace.lines().nth(2).unwrap().ends_with("2: addi 0 1 0       [3, 5, 6, 0, 0, 0]"));
+
+            let mut debugger = Debugger::new(Vm::new(&program, 6));
+            let mut output = Vec::new();
+            debugger.repl("break 6\ncontinue\nset r1 7\nstep\nwatch r9\n".as_bytes(), &mut output).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            assert!(output.contains("Breakpoint at 6\nip=6 steps=4 next: seti 9 0 5 registers: [5, 5, 6, 0, 0, 0]"));
+            assert!(output.contains("ip=7 steps=5 next: halted registers: [6, 7, 6, 0, 0, 9]"));
+            assert!(output.contains("Invalid register \"r9\""));
+        }
+
+        #[test]
+        fn finds_nested_loops() {
+            let program: Program = "
+                #ip 5
+                        seti 0 0 1
+                outer:  seti 0 0 2
+                inner:  addi 2 1 2
+                        gtri 2 3 3
+                        addr 3 ip ip
+                        seti inner-1 0 ip
+                        addi 1 1 1
+                        gtri 1 3 3
+                        addr 3 ip ip
+                        seti outer-1 0 ip
+            ".parse().unwrap();
+            let cfg = Cfg::new(&program);
+            let starts: Vec<usize> = cfg.blocks.iter().map(|b| b.start).collect();
+            assert_eq!(starts, [0, 1, 2, 5, 6, 9]);
+            assert_eq!(cfg.blocks[2].successors, [3, 4]);
+            assert!(cfg.blocks[4].exits);
+            let loops: Vec<(usize, Vec<usize>)> = cfg.loops.iter()
+                .map(|l| (l.header, l.body.iter().copied().collect()))
+                .collect();
+            assert_eq!(loops, [(1, vec![1, 2, 3, 4, 5]), (2, vec![2, 3])]);
+            assert_eq!(cfg.depth(3), 2);
+            let dot = cfg.to_dot();
+            assert!(dot.contains("b3 -> b2 [color=red];"));
+            assert!(dot.contains("b2 [label=\"2: r2 = r2 + 1\\l3: r3 = r2 > 3\\l4: if r2 > 3 goto 6\\l\", peripheries=2];"));
+            for empty in ["#ip 0", "; nothing here"] {
+                let cfg = Cfg::new(&empty.parse().unwrap());
+                assert!(cfg.blocks.is_empty() && cfg.loops.is_empty());
+            }
+        }
+
+        #[test]
+        fn optimized_runs_match_plain_runs() {
+            let divisors = "
+                #ip 3
+                        seti 36 0 4
+                        seti 1 0 2
+                outer:  seti 1 0 5
+                inner:  mulr 5 2 1
+                        eqrr 4 1 1
+                        addr 1 ip ip
+                        addi ip 1 ip
+                        addr 0 2 0
+                        addi 5 1 5
+                        gtrr 5 4 1
+                        addr ip 1 ip
+                        seti inner-1 0 ip
+                        addi 2 1 2
+                        gtrr 2 4 1
+                        addr 1 ip ip
+                        seti outer-1 0 ip
+            ";
+            let loops = "
+                #ip 1
+                        seti 7 0 2
+                        seti 40 0 3
+                times:  addr 0 2 0
+                        addi 4 1 4
+                        gtrr 4 3 5
+                        addr 5 ip ip
+                        seti times-1 0 ip
+                        seti 70000 0 3
+                divide: addi 4 1 5
+                        muli 5 256 5
+                        gtrr 5 3 5
+                        addr 5 ip ip
+                        addi ip 1 ip
+                        seti done-1 0 ip
+                        addi 4 1 4
+                        seti divide-1 0 ip
+                done:   setr 4 0 3
+            ";
+            for (source, idioms) in [(divisors, 2), (loops, 2)] {
+                let program: Program = source.parse().unwrap();
+                let mut plain = Vm::new(&program, 6);
+                plain.run();
+                let mut fast = Vm::new(&program, 6);
+                assert_eq!(fast.optimize(), idioms);
+                fast.run();
+                assert_eq!(fast.registers, plain.registers);
+                assert!(fast.steps * 10 < plain.steps);
+            }
+
+            let stray = "#ip 0\nseti 3 0 3\nloop: addr 1 2 2\naddi 5 1 5\ngtrr 5 3 4\naddr 4 ip ip\nseti loop-1 0 ip";
+            let mut vm = Vm::new(&stray.parse().unwrap(), 5);
+            assert_eq!(vm.optimize(), 0);
+            assert_eq!((vm.try_step(), vm.try_step()), (Ok(true), Ok(true)));
+            assert_eq!(vm.try_step(), Err(VmError::Register { at: 2, register: 5, count: 5 }));
+
+            let endless: Program = "#ip 0\nseti 0 0 3\nloop: addr 1 2 2\naddi 5 1 5\ngtrr 5 3 4\naddr 4 ip ip\nseti loop-1 0 ip"
+                .parse().unwrap();
+            let (mut plain, mut fast) = (Vm::new(&endless, 6), Vm::new(&endless, 6));
+            assert_eq!(fast.optimize(), 1);
+            for vm in [&mut plain, &mut fast] {
+                vm.step();
+                vm.registers[3] = usize::MAX;
+                vm.registers[5] = usize::MAX - 2;
+                for _ in 0..20 {
+                    vm.step();
+                }
+            }
+            assert_eq!(fast.registers, plain.registers);
+            assert_eq!(fast.registers[5], 1);
+        }
+
+        #[test]
+        fn compiled_matches_vm() {
+            let program: Program = "#ip 0\nseti 5 0 1\nseti 6 0 2\naddi 0 1 0\naddr 1 2 3\n\
+                setr 1 0 0\nseti 8 0 4\nseti 9 0 5".parse().unwrap();
+            let compiled = Compiled::new(&program);
+            let mut registers = [0; 6];
+            assert_eq!(compiled.run_until(&mut registers, 0, |ip, _| ip == 4), (4, 3));
+            assert_eq!(compiled.run_until(&mut registers, 4, |_, _| false), (7, 2));
+            let mut vm = Vm::new(&program, 6);
+            vm.run();
+            assert_eq!(registers[..], vm.registers[..]);
+
+            let start = [usize::MAX, 3, 1 << 40, 0];
+            for arithmetic in [Arithmetic::Wrapping, Arithmetic::Checked, Arithmetic::Bits(8)] {
+                for op in Opcode::ALL {
+                    let program = Program { ip: Some(3), instructions: vec![Instruction::new(op, 0, 2, 1)] };
+                    let mut vm = Vm::new(&program, 4);
+                    vm.arithmetic = arithmetic;
+                    vm.registers = start.to_vec();
+                    let expected = vm.try_run().map(|_| vm.registers);
+                    let mut registers = start;
+                    let found = Compiled::with_arithmetic(&program, arithmetic)
+                        .try_run_until(&mut registers, 0, |_, _| false)
+                        .map(|_| registers.to_vec());
+                    assert_eq!(found, expected, "{} {:?}", op, arithmetic);
+                }
+            }
+        }
+
+        #[test]
+        fn arithmetic_modes_and_register_checks() {
+            assert_eq!(load("#ip 0\n; comment\nseti 1 0 0\naddr 1 6 2", 6),
+                       Err(ParseProgramError::Register { line: 4, register: 6, count: 6 }));
+            assert_eq!(load("#ip 6", 6), Err(ParseProgramError::Register { line: 1, register: 6, count: 6 }));
+            let program = load(&format!("seti {} 0 0\naddi 0 2 1\nmuli 1 3 1", usize::MAX), 2).unwrap();
+            let run = |arithmetic| {
+                let mut vm = Vm::new(&program, 2);
+                vm.arithmetic = arithmetic;
+                vm.try_run().map(|_| vm.registers)
+            };
+            assert_eq!(run(Arithmetic::Wrapping), Ok(vec![usize::MAX, 3]));
+            assert_eq!(run(Arithmetic::Bits(8)), Ok(vec![255, 3]));
+            assert_eq!(run(Arithmetic::Checked), Err(VmError::Overflow {
+                at: 1, instruction: program.instructions[1], registers: vec![usize::MAX, 0],
+            }));
+            let mut vm = Vm::new(&program, 1);
+            assert_eq!(vm.try_step(), Ok(true));
+            assert_eq!(vm.try_step(), Err(VmError::Register { at: 1, register: 1, count: 1 }));
+        }
+
+        #[test]
+        fn infers_opcodes_from_samples() {
+            let sample = |before: [usize; 4], instruction, after: [usize; 4]| Sample {
+                before: before.to_vec(), instruction, after: after.to_vec(),
+            };
+            let example = sample([3, 2, 1, 1], [9, 2, 1, 2], [3, 2, 2, 1]);
+            assert_eq!(example.candidates(), [Addi, Mulr, Seti]);
+            match infer(std::slice::from_ref(&example), 10) {
+                Inference::Ambiguous(mappings) => assert_eq!(mappings.len(), 3),
+                other => panic!("Expected ambiguity, got {:?}", other),
+            }
+            let addi = sample([0, 5, 0, 0], [9, 1, 3, 0], [8, 5, 0, 0]);
+            assert_eq!(infer(&[example.clone(), addi], 10), Inference::Unique(Mapping::from([(9, Addi)])));
+
+            let impossible = sample([0, 0, 0, 0], [9, 0, 0, 0], [5, 0, 0, 0]);
+            match infer(&[example, impossible], 10) {
+                Inference::Contradiction(codes) => {
+                    assert!(codes[&9].contains(&(Addi, 1)));
+                    assert!(codes[&9].contains(&(Gtrr, 0)));
+                }
+                other => panic!("Expected a contradiction, got {:?}", other),
+            }
+            let only_seti = |code| sample([0, 0, 0, 0], [code, 7, 9, 0], [7, 0, 0, 0]);
+            assert_eq!(infer(&[only_seti(1), only_seti(2)], 10), Inference::Unassignable(
+                BTreeMap::from([(1, vec![Seti]), (2, vec![Seti])])));
+        }
+
+        #[test]
+        fn assembles_and_disassembles() {
+            let source = "
+                #ip r2
+                #reg value r3        ; the number being hashed
+                start: seti 123 0 value
+                check:
+                    bani value 456 value
+                    eqri value 72 value
+                    addr value ip ip
+                    seti check-1 0 ip
+                    seti 0 0 value
+                    mulr ip ip ip
+            ";
+            let program: Program = source.parse().unwrap();
+            assert_eq!(program.ip, Some(2));
+            assert_eq!(program.instructions[4], Instruction::new(Seti, 0, 0, 2));
+            assert_eq!(disassemble(&program), concat!(
+                "#ip 2\n",
+                "  0: r3 = 123\n",
+                "  1: r3 = r3 & 456\n",
+                "  2: r3 = r3 == 72\n",
+                "  3: if r3 goto 5\n",
+                "  4: goto 1\n",
+                "  5: r3 = 0\n",
+                "  6: halt\n",
+            ));
+            let compare: Program = "#ip 0\ngtrr 1 2 3\naddr 3 0 0\nseti 9 0 0\nseti 0 0 3".parse().unwrap();
+            assert_eq!(disassemble(&compare),
+                       "#ip 0\n  0: r3 = r1 > r2\n  1: if r1 > r2 goto 3\n  2: halt\n  3: r3 = 0\n");
+            assert_eq!("seti done 0 ip".parse::<Program>(),
+                       Err(ParseProgramError::Symbol { line: 1, name: "done".to_string() }));
+            for (source, name) in [("r1: seti 0 0 0\nseti r1 0 1", "r1"), ("#reg ip r2", "ip"), ("7: seti 0 0 0", "7")] {
+                assert_eq!(source.parse::<Program>(),
+                           Err(ParseProgramError::Reserved { line: 1, name: name.to_string() }));
+            }
+            assert!("r1x: seti r1x 0 0".parse::<Program>().is_ok());
+        }
+    }
+}
+
+pub mod fuzz {
+    use std::collections::HashMap;
+    use std::fmt;
+
+    use super::op_code::{self, Compiled, Inference, Instruction, Mapping, Opcode, Program, Sample, Vm};
+
+    /// A xorshift generator, so a seed always produces the same cases.
+    pub struct Rng(u64);
+
+    impl Rng {
+        pub fn new(seed: u64) -> Rng {
+            Rng(seed | 1)
+        }
+
+        pub fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        pub fn below(&mut self, n: usize) -> usize {
+            (self.next_u64() % n as u64) as usize
+        }
+
+        pub fn shuffle<T>(&mut self, items: &mut [T]) {
+            for i in (1..items.len()).rev() {
+                items.swap(i, self.below(i + 1));
+            }
+        }
+    }
+
+    type Semantics = fn(u64, u64) -> u64;
+
+    /// The opcodes as the puzzle describes them, with the kind of each
+    /// operand: `r` for a register, `i` for an immediate, `-` if ignored.
+    /// Written apart from `Opcode::eval` so the two can be checked against
+    /// each other.
+    const REFERENCE: [(&str, &str, Semantics); 16] = [
+        ("addr", "rr", |x, y| x.wrapping_add(y)),
+        ("addi", "ri", |x, y| x.wrapping_add(y)),
+        ("mulr", "rr", |x, y| x.wrapping_mul(y)),
+        ("muli", "ri", |x, y| x.wrapping_mul(y)),
+        ("banr", "rr", |x, y| x & y),
+        ("bani", "ri", |x, y| x & y),
+        ("borr", "rr", |x, y| x | y),
+        ("bori", "ri", |x, y| x | y),
+        ("setr", "r-", |x, _| x),
+        ("seti", "i-", |x, _| x),
+        ("gtir", "ir", |x, y| if x > y { 1 } else { 0 }),
+        ("gtri", "ri", |x, y| if x > y { 1 } else { 0 }),
+        ("gtrr", "rr", |x, y| if x > y { 1 } else { 0 }),
+        ("eqir", "ir", |x, y| if x == y { 1 } else { 0 }),
+        ("eqri", "ri", |x, y| if x == y { 1 } else { 0 }),
+        ("eqrr", "rr", |x, y| if x == y { 1 } else { 0 }),
+    ];
+
+    /// The registers after `op` runs on `before`, with every value read and
+    /// written truncated to `bits` bits.
+    pub fn reference(op: Opcode, [a, b, c]: [usize; 3], before: &[u64], bits: u32) -> Vec<u64> {
+        let mask = if bits >= 64 { u64::MAX } else { (1 << bits) - 1 };
+        let (_, kinds, f) = REFERENCE.iter().find(|(name, _, _)| *name == op.name()).unwrap();
+        let mut after: Vec<u64> = before.iter().map(|v| v & mask).collect();
+        let operand = |kind: u8, value: usize| match kind {
+            b'r' => after[value],
+            b'i' => value as u64 & mask,
+            _ => 0,
+        };
+        let value = f(operand(kinds.as_bytes()[0], a), operand(kinds.as_bytes()[1], b));
+        after[c] = value & mask;
+        after
+    }
+
+    /// Runs `program` one instruction at a time through `reference`.
+    fn run_reference(program: &Program, registers: &[usize], limit: u64) -> Option<Vec<usize>> {
+        let mut registers: Vec<u64> = registers.iter().map(|&v| v as u64).collect();
+        let mut ip = 0;
+        for _ in 0..limit {
+            let instruction = match program.instructions.get(ip) {
+                Some(instruction) => instruction,
+                None => return Some(registers.iter().map(|&v| v as usize).collect()),
+            };
+            if let Some(reg) = program.ip {
+                registers[reg] = ip as u64;
+            }
+            registers = reference(instruction.op, instruction.args, &registers, usize::BITS);
+            ip = match program.ip {
+                Some(reg) => registers[reg] as usize,
+                None => ip,
+            } + 1;
+        }
+        None
+    }
+
+    /// Generates ElfCode programs and day 16 style samples whose register
+    /// operands are always in range.
+    pub struct Fuzzer {
+        pub rng: Rng,
+        registers: usize,
+        /// Immediates and starting register values in programs are below this.
+        values: usize,
+    }
+
+    impl Fuzzer {
+        pub fn new(seed: u64, registers: usize) -> Fuzzer {
+            Fuzzer { rng: Rng::new(seed), registers, values: 16 }
+        }
+
+        fn operand(&mut self, is_reg: bool) -> usize {
+            self.rng.below(if is_reg { self.registers } else { self.values })
+        }
+
+        /// A value from anywhere in the 64-bit range, biased toward the edges
+        /// where widths and overflow behave differently.
+        fn wide(&mut self) -> u64 {
+            match self.rng.below(4) {
+                0 => self.rng.below(self.values) as u64,
+                1 => u64::MAX - self.rng.below(self.values) as u64,
+                2 => (1 << self.rng.below(64)) - self.rng.below(2) as u64,
+                _ => self.rng.next_u64(),
+            }
+        }
+
+        pub fn instruction(&mut self, op: Opcode) -> Instruction {
+            let [reg_a, reg_b] = op.register_args();
+            let (a, b) = (self.operand(reg_a), self.operand(reg_b));
+            Instruction::new(op, a, b, self.operand(true))
+        }
+
+        pub fn registers(&mut self) -> Vec<usize> {
+            (0..self.registers).map(|_| self.operand(false)).collect()
+        }
+
+        /// An optimizer idiom placed at `start`, with its register letters
+        /// bound to distinct registers other than `ip`, or None if there are
+        /// too few registers.
+        fn idiom(&mut self, template: &str, start: usize, ip: usize) -> Option<Vec<Instruction>> {
+            let mut free: Vec<usize> = (0..self.registers).filter(|&r| r != ip).collect();
+            self.rng.shuffle(&mut free);
+            let mut bound: HashMap<char, usize> = HashMap::new();
+            let mut instructions = Vec::new();
+            for line in template.lines() {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                let mut args = [0; 3];
+                for (arg, token) in args.iter_mut().zip(&words[1..]) {
+                    let var = token.chars().next().unwrap();
+                    *arg = match *token {
+                        "ip" => ip,
+                        "_" => self.operand(false),
+                        _ if var == '@' => start + token[1..].parse::<usize>().unwrap() - 1,
+                        _ if var.is_ascii_digit() => token.parse().unwrap(),
+                        _ => match bound.get(&var) {
+                            Some(&value) => value,
+                            None => {
+                                let value = match var.is_ascii_uppercase() {
+                                    true => free.pop()?,
+                                    false => self.operand(false),
+                                };
+                                bound.insert(var, value);
+                                value
+                            }
+                        },
+                    };
+                }
+                instructions.push(Instruction::new(words[0].parse().unwrap(), args[0], args[1], args[2]));
+            }
+            Some(instructions)
+        }
+
+        /// A program of up to `max_len` random instructions, bound to an ip
+        /// register half of the time. Half of the bound programs also have
+        /// one of the optimizer's idioms spliced in, after at least one
+        /// random instruction, so superinstructions get run.
+        pub fn program(&mut self, max_len: usize) -> Program {
+            let ip = match self.rng.below(2) {
+                0 => None,
+                _ => Some(self.operand(true)),
+            };
+            let len = 1 + self.rng.below(max_len);
+            let mut instructions: Vec<Instruction> = (0..len)
+                .map(|_| {
+                    let op = Opcode::ALL[self.rng.below(Opcode::ALL.len())];
+                    self.instruction(op)
+                })
+                .collect();
+            if let (Some(ip), 0) = (ip, self.rng.below(2)) {
+                let templates: Vec<&str> = op_code::idiom_templates().collect();
+                let template = templates[self.rng.below(templates.len())];
+                let start = 1 + self.rng.below(len);
+                if let Some(idiom) = self.idiom(template, start, ip) {
+                    instructions.splice(start..start, idiom);
+                }
+            }
+            Program { ip, instructions }
+        }
+
+        /// Samples under a random numbering of the opcodes, with operands
+        /// and register values from the whole 64-bit range, each observed by
+        /// running the instruction through `reference`.
+        pub fn samples(&mut self, count: usize) -> (Mapping, Vec<Sample<u64>>) {
+            let mut ops = Opcode::ALL;
+            self.rng.shuffle(&mut ops);
+            let mapping: Mapping = ops.iter().copied().enumerate().collect();
+            let samples = (0..count).map(|_| {
+                let code = self.rng.below(ops.len());
+                let [reg_a, reg_b] = ops[code].register_args();
+                let mut operand = |is_reg| if is_reg { self.operand(true) } else { self.wide() as usize };
+                let args = [operand(reg_a), operand(reg_b), operand(true)];
+                let before: Vec<u64> = (0..self.registers).map(|_| self.wide()).collect();
+                let after = reference(ops[code], args, &before, 64);
+                Sample { before, instruction: [code, args[0], args[1], args[2]], after }
+            }).collect();
+            (mapping, samples)
+        }
+    }
+
+    /// A runner that disagreed with the plain `Vm`.
+    #[derive(Debug)]
+    pub struct Mismatch {
+        pub runner: &'static str,
+        pub program: Program,
+        pub registers: Vec<usize>,
+        pub expected: Vec<usize>,
+        /// None if it did not halt.
+        pub found: Option<Vec<usize>>,
+    }
+
+    impl fmt::Display for Mismatch {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match &self.found {
+                Some(found) => write!(f, "{} runner ended with {:?} instead of {:?}", self.runner, found, self.expected)?,
+                None => write!(f, "{} runner did not halt", self.runner)?,
+            }
+            write!(f, " starting from {:?} on\n{}", self.registers, self.program)
+        }
+    }
+
+    /// Runs `program` from `registers` on the plain `Vm` for at most `limit`
+    /// steps and, if it halted, checks that the reference, compiled and
+    /// optimized runners end with the same registers. Returns the steps the
+    /// optimized runner saved, or None if the program did not halt.
+    pub fn differential(program: &Program, registers: &[usize], limit: u64) -> Result<Option<u64>, Box<Mismatch>> {
+        let mut vm = Vm::new(program, registers.len());
+        vm.registers = registers.to_vec();
+        if vm.run_until(|vm| vm.steps >= limit) {
+            return Ok(None);
+        }
+        let mut compiled = registers.to_vec();
+        let mut steps = 0;
+        let (ip, _) = Compiled::new(program).run_until(&mut compiled, 0, |_, _| {
+            steps += 1;
+            steps > limit
+        });
+        let mut optimized = Vm::new(program, registers.len());
+        optimized.registers = registers.to_vec();
+        optimized.optimize();
+        let stuck = optimized.run_until(|vm| vm.steps >= limit);
+        let runs = vec![
+            ("reference", run_reference(program, registers, limit)),
+            ("compiled", Some(compiled).filter(|_| ip >= program.instructions.len())),
+            ("optimized", Some(optimized.registers).filter(|_| !stuck)),
+        ];
+        for (runner, found) in runs {
+            if found.as_ref() != Some(&vm.registers) {
+                return Err(Box::new(Mismatch {
+                    runner,
+                    program: program.clone(),
+                    registers: registers.to_vec(),
+                    expected: vm.registers,
+                    found,
+                }));
+            }
+        }
+        Ok(Some(vm.steps - optimized.steps))
+    }
+
+    /// Checks that samples match `reference`, that `Opcode::eval` agrees
+    /// with it at 64 and 32 bits, and that inference recovers `mapping`.
+    pub fn check_samples(mapping: &Mapping, samples: &[Sample<u64>]) -> Result<(), String> {
+        for (index, sample) in samples.iter().enumerate() {
+            let op = mapping[&sample.instruction[0]];
+            let [_, a, b, c] = sample.instruction;
+            if reference(op, [a, b, c], &sample.before, 64) != sample.after {
+                return Err(format!("Sample {} is not what {} does: {:?}", index, op, sample));
+            }
+            if !sample.fits(op) {
+                return Err(format!("{} disagrees with the reference on sample {}: {:?}", op, index, sample));
+            }
+            let narrow = Sample {
+                before: sample.before.iter().map(|&v| v as u32).collect(),
+                instruction: sample.instruction,
+                after: reference(op, [a, b, c], &sample.before, 32).iter().map(|&v| v as u32).collect(),
+            };
+            if !narrow.fits(op) {
+                return Err(format!("{} disagrees with the reference at 32 bits on sample {}: {:?}", op, index, sample));
+            }
+        }
+        match op_code::infer(samples, 2) {
+            Inference::Unique(found) if found == *mapping => Ok(()),
+            other => Err(format!("Inferred {} from samples of {:?}", other, mapping)),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn fixed_seed_finds_no_drift() {
+            let mut fuzzer = Fuzzer::new(2018, 6);
+            let (mut halted, mut accelerated) = (0, 0);
+            for _ in 0..1000 {
+                let program = fuzzer.program(12);
+                let registers = fuzzer.registers();
+                match differential(&program, &registers, 10_000) {
+                    Ok(Some(saved)) => {
+                        halted += 1;
+                        accelerated += (saved > 0) as usize;
+                    }
+                    Ok(None) => (),
+                    Err(mismatch) => panic!("{}", mismatch),
+                }
+            }
+            assert!(halted > 100);
+            assert!(accelerated > 20, "Only {} programs ran a superinstruction", accelerated);
+            for _ in 0..20 {
+                let (mapping, samples) = fuzzer.samples(400);
+                check_samples(&mapping, &samples).unwrap();
             }
-        };
-    );
-
-    pub fn new() -> HashMap<String, &'static Op> {
-        opcode_map! {
-            addr: |c: Code, r: Val| r[c[0]] + r[c[1]],
-            addi: |c: Code, r: Val| r[c[0]] + c[1],
-            mulr: |c: Code, r: Val| r[c[0]] * r[c[1]],
-            muli: |c: Code, r: Val| r[c[0]] * c[1],
-            banr: |c: Code, r: Val| r[c[0]] & r[c[1]],
-            bani: |c: Code, r: Val| r[c[0]] & c[1],
-            borr: |c: Code, r: Val| r[c[0]] | r[c[1]],
-            bori: |c: Code, r: Val| r[c[0]] | c[1],
-            setr: |c: Code, r: Val| r[c[0]],
-            seti: |c: Code, _: Val| c[0],
-            gtir: |c: Code, r: Val| if c[0] > r[c[1]] { 1 } else { 0 },
-            gtri: |c: Code, r: Val| if r[c[0]] > c[1] { 1 } else { 0 },
-            gtrr: |c: Code, r: Val| if r[c[0]] > r[c[1]] { 1 } else { 0 },
-            eqir: |c: Code, r: Val| if c[0] == r[c[1]] { 1 } else { 0 },
-            eqri: |c: Code, r: Val| if r[c[0]] == c[1] { 1 } else { 0 },
-            eqrr: |c: Code, r: Val| if r[c[0]] == r[c[1]] { 1 } else { 0 }
         }
     }
 }